@@ -0,0 +1,37 @@
+//! Times `Algorithm::Rounds` on one large instance with 1, 2, 4 and 8 threads
+//!
+//! cargo run --release --features parallel --example parallel_rounds [men] [seed]
+
+use std::env;
+use std::time::Instant;
+
+use matchertools::{generator, Solver};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let size: usize = args.next().map_or(1000, |arg| arg.parse().unwrap());
+    let seed: u64 = args.next().map_or(1, |arg| arg.parse().unwrap());
+
+    let instance = generator::uniform(size, size, seed);
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+
+    let mut serial = None;
+    for threads in [1, 2, 4, 8].iter() {
+        let started = Instant::now();
+        let (engaged_man_woman, statistics) = Solver::new()
+            .threads(*threads)
+            .solve_with_statistics(&men_preferences, &women_preferences);
+        let elapsed = started.elapsed();
+
+        match &serial {
+            None => serial = Some((engaged_man_woman, elapsed)),
+            Some((serial_engagements, _)) => assert_eq!(&engaged_man_woman, serial_engagements),
+        }
+        let speedup = serial.as_ref().unwrap().1.as_secs_f64() / elapsed.as_secs_f64();
+        println!(
+            "{} threads: {:?} for {} rounds, {:.2}x",
+            threads, elapsed, statistics.rounds, speedup
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::thread;
+
+use crate::{Instance, Solver};
+
+impl Solver {
+    /**
+        Solves every instance, spread over `threads` threads. Needs the `parallel` feature
+
+        Each thread takes a contiguous run of instances and solves them with its own copy of the
+        solver, as `solve_reusing` does. No more threads than instances are started, and 0 threads
+        counts as 1. The matchings come back in the order of `instances`, and they are the same
+        whatever the number of threads.
+
+        # Examples
+        ```
+        use matchertools::{generator, Solver};
+
+        let instances: Vec<_> = (0..20).map(|seed| generator::uniform(10, 10, seed)).collect();
+        let matchings = Solver::new().solve_batch(&instances, 4);
+
+        assert_eq!(matchings.len(), 20);
+        for (instance, matching) in instances.iter().zip(&matchings) {
+            let men_preferences = instance.men_preferences();
+            let women_preferences = instance.women_preferences();
+            let expected = matchertools::gale_shapley(&men_preferences, &women_preferences);
+            assert_eq!(matching, &expected);
+        }
+        ```
+    */
+    pub fn solve_batch<'a, T>(
+        &self,
+        instances: &'a [Instance<T>],
+        threads: usize,
+    ) -> Vec<HashMap<&'a T, &'a T>>
+    where
+        T: Eq + Hash + Sync,
+    {
+        if instances.is_empty() {
+            return Vec::new();
+        }
+        let chunk_size = instances.len().div_ceil(threads.max(1));
+
+        return thread::scope(|scope| {
+            let handles: Vec<_> = instances
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let mut solver = self.clone();
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|instance| solve_instance(&mut solver, instance))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        });
+    }
+}
+
+/// Solves one instance, with the matching borrowing from the instance rather than from its borrowed preferences
+fn solve_instance<'a, T>(solver: &mut Solver, instance: &'a Instance<T>) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+{
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+
+    return solver
+        .solve_reusing(&men_preferences, &women_preferences)
+        .into_iter()
+        .map(|(man, woman)| {
+            let (man, _) = instance.men.get_key_value(man).unwrap();
+            let (woman, _) = instance.women.get_key_value(woman).unwrap();
+            (man, woman)
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator;
+
+    #[test]
+    fn test_results_do_not_depend_on_thread_count() {
+        let instances: Vec<Instance<u32>> = (0..25)
+            .map(|seed| generator::incomplete(8, 7, 0.6, seed))
+            .collect();
+
+        let serial = Solver::new().solve_batch(&instances, 1);
+        for threads in [0, 2, 3, 8, 100].iter() {
+            assert_eq!(Solver::new().solve_batch(&instances, *threads), serial);
+        }
+
+        for (instance, matching) in instances.iter().zip(&serial) {
+            let men_preferences = instance.men_preferences();
+            let women_preferences = instance.women_preferences();
+            assert_eq!(
+                matching,
+                &crate::gale_shapley(&men_preferences, &women_preferences)
+            );
+        }
+        assert!(Solver::new().solve_batch::<u32>(&[], 4).is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+//! An exhaustive solver for small instances, to check the real ones against
+//!
+//! Every matching of acceptable pairs is tried, so the cost grows like `n!`. Matchings that are
+//! already blocked are cut off early, which makes instances with up to 9 men and 9 women take well
+//! under a second.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::rotations::rank_tables;
+use crate::Interned;
+
+/**
+    Every stable matching, in no particular order
+
+    Preferences can be incomplete. A man and a woman can only be matched if they are on each
+    other's list, and since a matching can leave people single, the stable matchings found this way
+    are exactly the ones `gale_shapley` and `closest_stable_matching` choose from.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::brute_force;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let stable_matchings = brute_force::stable_matchings(&men_preferences, &women_preferences);
+    assert_eq!(stable_matchings.len(), 2);
+    ```
+*/
+pub fn stable_matchings<'a, T>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+) -> Vec<HashMap<&'a T, &'a T>>
+where
+    T: Eq + Hash,
+{
+    let interned = Interned::new(input_men_preferences, input_women_preferences);
+    let men_ranks = rank_tables(&interned.men_preferences);
+    let women_ranks = rank_tables(&interned.women_preferences);
+
+    let mut search = Search {
+        men_ranks: &men_ranks,
+        women_ranks: &women_ranks,
+        men_preferences: &interned.men_preferences,
+        wives: vec![None; interned.men.len()],
+        husbands: vec![None; interned.women.len()],
+        found: Vec::new(),
+    };
+    search.assign(0);
+
+    return search
+        .found
+        .iter()
+        .map(|engaged_man_woman| interned.engagements_from_u32(engaged_man_woman))
+        .collect();
+}
+
+/**
+    The stable matching that every man likes at least as much as any other stable matching
+
+    This is what `gale_shapley` should return.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::brute_force;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    assert_eq!(
+        brute_force::man_optimal(&men_preferences, &women_preferences),
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+    ```
+*/
+pub fn man_optimal<'a, T>(
+    men_preferences: &'a HashMap<&T, Vec<&T>>,
+    women_preferences: &'a HashMap<&T, Vec<&T>>,
+) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+{
+    return best_for(
+        stable_matchings(men_preferences, women_preferences),
+        men_preferences,
+    );
+}
+
+/// The stable matching that every woman likes at least as much as any other stable matching. Maps each man to a woman, like `man_optimal`
+pub fn woman_optimal<'a, T>(
+    men_preferences: &'a HashMap<&T, Vec<&T>>,
+    women_preferences: &'a HashMap<&T, Vec<&T>>,
+) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+{
+    let engaged_woman_man = best_for(
+        stable_matchings(men_preferences, women_preferences)
+            .into_iter()
+            .map(|matching| {
+                matching
+                    .into_iter()
+                    .map(|(man, woman)| (woman, man))
+                    .collect()
+            })
+            .collect(),
+        women_preferences,
+    );
+
+    return engaged_woman_man
+        .into_iter()
+        .map(|(woman, man)| (man, woman))
+        .collect();
+}
+
+/// The matching with the lowest total rank for the agents that `preferences` belong to. Everyone is
+/// matched in the same stable matchings, so that is the one that is best for each of them
+fn best_for<'a, T>(
+    matchings: Vec<HashMap<&'a T, &'a T>>,
+    preferences: &HashMap<&T, Vec<&T>>,
+) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+{
+    let total_rank = |matching: &HashMap<&T, &T>| -> usize {
+        return matching
+            .iter()
+            .map(|(agent, partner)| {
+                preferences
+                    .get(*agent)
+                    .unwrap()
+                    .iter()
+                    .position(|choice| choice == partner)
+                    .unwrap()
+            })
+            .sum();
+    };
+
+    // There always is at least one stable matching
+    return matchings.into_iter().min_by_key(total_rank).unwrap();
+}
+
+/// Depth-first search over the men, trying every woman (or nobody) for each
+struct Search<'s> {
+    men_ranks: &'s HashMap<u32, HashMap<u32, usize>>,
+    women_ranks: &'s HashMap<u32, HashMap<u32, usize>>,
+    men_preferences: &'s HashMap<u32, Vec<u32>>,
+    wives: Vec<Option<u32>>,
+    husbands: Vec<Option<u32>>,
+    found: Vec<HashMap<u32, u32>>,
+}
+
+impl<'s> Search<'s> {
+    fn assign(&mut self, man: u32) {
+        if man as usize == self.wives.len() {
+            if self.no_single_woman_blocks() {
+                self.found.push(
+                    self.wives
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(man, wife)| wife.map(|woman| (man as u32, woman)))
+                        .collect(),
+                );
+            }
+            return;
+        }
+
+        let men_preferences = self.men_preferences;
+        for woman in men_preferences.get(&man).unwrap() {
+            if self.husbands[*woman as usize].is_some() || self.rank_of_man(*woman, man).is_none() {
+                continue;
+            }
+
+            self.wives[man as usize] = Some(*woman);
+            self.husbands[*woman as usize] = Some(man);
+            if !self.blocked_by_married(man) {
+                self.assign(man + 1);
+            }
+            self.wives[man as usize] = None;
+            self.husbands[*woman as usize] = None;
+        }
+
+        if !self.blocked_by_married(man) {
+            self.assign(man + 1);
+        }
+    }
+
+    /// Returns true if `man` and an earlier man's wife, or his own wife and an earlier man, would
+    /// both rather be together. Single women are checked once everyone is assigned
+    fn blocked_by_married(&self, man: u32) -> bool {
+        for other in 0..man {
+            if let Some(woman) = self.wives[other as usize] {
+                if self.prefers(man, woman) {
+                    return true;
+                }
+            }
+            if let Some(woman) = self.wives[man as usize] {
+                if self.prefers(other, woman) {
+                    return true;
+                }
+            }
+        }
+
+        return false;
+    }
+
+    fn no_single_woman_blocks(&self) -> bool {
+        for (woman, husband) in self.husbands.iter().enumerate() {
+            if husband.is_none() {
+                for man in 0..self.wives.len() as u32 {
+                    if self.prefers(man, woman as u32) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        return true;
+    }
+
+    /// Returns true if `man` and `woman` find each other acceptable and both prefer each other to their current partner
+    fn prefers(&self, man: u32, woman: u32) -> bool {
+        let rank_woman = match self.men_ranks.get(&man).unwrap().get(&woman) {
+            Some(rank) => *rank,
+            None => return false,
+        };
+        let rank_man = match self.rank_of_man(woman, man) {
+            Some(rank) => rank,
+            None => return false,
+        };
+
+        let man_prefers = match self.wives[man as usize] {
+            Some(wife) => rank_woman < *self.men_ranks.get(&man).unwrap().get(&wife).unwrap(),
+            None => true,
+        };
+        let woman_prefers = match self.husbands[woman as usize] {
+            Some(husband) => rank_man < self.rank_of_man(woman, husband).unwrap(),
+            None => true,
+        };
+
+        return man_prefers && woman_prefers;
+    }
+
+    fn rank_of_man(&self, woman: u32, man: u32) -> Option<usize> {
+        return self.women_ranks.get(&woman).unwrap().get(&man).cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_incomplete_lists_leave_agents_single() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0]);
+        men_preferences.insert(&1, vec![&0, &1]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&1, &0]);
+        women_preferences.insert(&1, vec![]);
+
+        let stable_matchings = stable_matchings(&men_preferences, &women_preferences);
+
+        assert_eq!(stable_matchings.len(), 1);
+        assert_eq!(stable_matchings[0].len(), 1);
+        assert_eq!(stable_matchings[0].get(&1), Some(&&0));
+    }
+
+    #[test]
+    fn test_optimal_matchings() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1, &2]);
+        men_preferences.insert(&1, vec![&1, &2, &0]);
+        men_preferences.insert(&2, vec![&2, &0, &1]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&1, &2, &0]);
+        women_preferences.insert(&1, vec![&2, &0, &1]);
+        women_preferences.insert(&2, vec![&0, &1, &2]);
+
+        assert_eq!(
+            stable_matchings(&men_preferences, &women_preferences).len(),
+            3
+        );
+
+        let man_optimal = man_optimal(&men_preferences, &women_preferences);
+        let woman_optimal = woman_optimal(&men_preferences, &women_preferences);
+        for man in 0..3 {
+            assert_eq!(man_optimal.get(&man), Some(&&man));
+            assert_eq!(woman_optimal.get(&man), Some(&&((man + 2) % 3)));
+        }
+    }
+
+    /// Backs the claim in the module documentation. Timings only mean something in a release build:
+    /// cargo test --release -- --ignored brute_force
+    #[test]
+    #[ignore]
+    fn test_nine_by_nine_takes_under_a_second() {
+        for seed in 0..5 {
+            for instance in [
+                generator::uniform(9, 9, seed),
+                generator::incomplete(9, 9, 0.5, seed),
+                generator::master_list(9, 9, 0.3, seed),
+            ]
+            .iter()
+            {
+                let men_preferences = instance.men_preferences();
+                let women_preferences = instance.women_preferences();
+
+                let started = Instant::now();
+                let stable_matchings = stable_matchings(&men_preferences, &women_preferences);
+                let elapsed = started.elapsed();
+
+                assert!(!stable_matchings.is_empty());
+                assert!(
+                    elapsed < Duration::from_secs(1),
+                    "{:?}: {:?}",
+                    instance,
+                    elapsed
+                );
+            }
+        }
+    }
+}
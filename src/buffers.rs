@@ -0,0 +1,273 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+
+/// Marks an unacceptable man in `women_ranks`, a single woman in `husbands`, and an empty slot in `Ids`
+const NONE: u32 = u32::MAX;
+
+/// The Gale-Shapley algorithm over flat arrays, which `Solver::solve_reusing` keeps allocated between solves
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Buffers {
+    men_ids: Ids,
+    women_ids: Ids,
+    men: usize,
+    /// Every man's list, one after the other
+    men_preferences: Vec<u32>,
+    /// Man i's list is men_preferences[men_offsets[i]..men_offsets[i + 1]]
+    men_offsets: Vec<usize>,
+    /// women_ranks[woman * men + man] is where the woman ranks the man, or NONE if he is unacceptable
+    women_ranks: Vec<u32>,
+    /// Index in `men_preferences` of the next woman each man proposes to
+    next_choice: Vec<usize>,
+    husbands: Vec<u32>,
+    free_men: Vec<u32>,
+}
+
+impl Buffers {
+    pub(crate) fn with_capacity(men: usize, women: usize) -> Buffers {
+        return Buffers {
+            men_ids: Ids::with_capacity(men),
+            women_ids: Ids::with_capacity(women),
+            men: 0,
+            men_preferences: Vec::with_capacity(men * women),
+            men_offsets: Vec::with_capacity(men + 1),
+            women_ranks: Vec::with_capacity(men * women),
+            next_choice: Vec::with_capacity(men),
+            husbands: Vec::with_capacity(women),
+            free_men: Vec::with_capacity(men),
+        };
+    }
+
+    /// Solves an instance given as `gale_shapley` takes it. Once the buffers are large enough, only the lists of men
+    /// and women and the returned matching are allocated
+    pub(crate) fn solve_instance<'a, T>(
+        &mut self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    ) -> HashMap<&'a T, &'a T>
+    where
+        T: Eq + Hash,
+    {
+        let men: Vec<&T> = input_men_preferences.keys().cloned().collect();
+        let women: Vec<&T> = input_women_preferences.keys().cloned().collect();
+
+        // taken out while the men and women are added, which borrows the buffers mutably
+        let mut men_ids = mem::take(&mut self.men_ids);
+        let mut women_ids = mem::take(&mut self.women_ids);
+        men_ids.reset(&men);
+        women_ids.reset(&women);
+
+        self.reset(men.len(), women.len());
+        for man in &men {
+            let choices = input_men_preferences.get(man).unwrap();
+            self.push_man(
+                choices
+                    .iter()
+                    .map(|woman| women_ids.get(&women, *woman).unwrap()),
+            );
+        }
+        for (id, woman) in women.iter().enumerate() {
+            let choices = input_women_preferences.get(woman).unwrap();
+            self.set_woman(
+                id as u32,
+                choices.iter().map(|man| men_ids.get(&men, *man).unwrap()),
+            );
+        }
+        self.solve();
+
+        let mut engaged_man_woman = HashMap::with_capacity(men.len().min(women.len()));
+        engaged_man_woman.extend(
+            self.engagements()
+                .map(|(man, woman)| (men[man as usize], women[woman as usize])),
+        );
+
+        self.men_ids = men_ids;
+        self.women_ids = women_ids;
+        return engaged_man_woman;
+    }
+
+    /// Forgets the previous instance. Men are then added with `push_man`, in id order, and women with `set_woman`
+    pub(crate) fn reset(&mut self, men: usize, women: usize) {
+        self.men = men;
+        self.men_preferences.clear();
+        self.men_offsets.clear();
+        self.men_offsets.push(0);
+        self.women_ranks.clear();
+        self.women_ranks.resize(men * women, NONE);
+        self.next_choice.clear();
+        self.husbands.clear();
+        self.husbands.resize(women, NONE);
+        self.free_men.clear();
+    }
+
+    pub(crate) fn push_man<I>(&mut self, preferences: I)
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        self.men_preferences.extend(preferences);
+        self.men_offsets.push(self.men_preferences.len());
+    }
+
+    pub(crate) fn set_woman<I>(&mut self, woman: u32, preferences: I)
+    where
+        I: IntoIterator<Item = u32>,
+    {
+        let row = woman as usize * self.men;
+        for (rank, man) in preferences.into_iter().enumerate() {
+            // Only the first time a man is listed counts, as in `get_rank`
+            if self.women_ranks[row + man as usize] == NONE {
+                self.women_ranks[row + man as usize] = rank as u32;
+            }
+        }
+    }
+
+    /// Free men propose one at a time, each until he is engaged or has run out of women. Any order gives the man-optimal matching
+    pub(crate) fn solve(&mut self) {
+        self.next_choice
+            .extend_from_slice(&self.men_offsets[..self.men]);
+        self.free_men.extend((0..self.men as u32).rev());
+
+        while let Some(man) = self.free_men.pop() {
+            let m = man as usize;
+            while self.next_choice[m] < self.men_offsets[m + 1] {
+                let woman = self.men_preferences[self.next_choice[m]] as usize;
+                self.next_choice[m] += 1;
+
+                let rank = self.women_ranks[woman * self.men + m];
+                if rank == NONE {
+                    continue;
+                }
+
+                let husband = self.husbands[woman];
+                if husband == NONE {
+                    self.husbands[woman] = man;
+                    break;
+                }
+                if rank < self.women_ranks[woman * self.men + husband as usize] {
+                    self.husbands[woman] = man;
+                    self.free_men.push(husband);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The (man, woman) pairs found by the last `solve`
+    pub(crate) fn engagements(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        return self
+            .husbands
+            .iter()
+            .enumerate()
+            .filter(|(_, husband)| **husband != NONE)
+            .map(|(woman, husband)| (*husband, woman as u32));
+    }
+}
+
+/// Agent ids by hash, so that interning needs no new `HashMap` per solve. Open addressing with linear probing
+#[derive(Debug, Clone, Default)]
+struct Ids {
+    hasher: RandomState,
+    /// A power of two number of slots, at least twice the number of agents. Each holds an id or NONE
+    slots: Vec<u32>,
+}
+
+impl Ids {
+    fn with_capacity(agents: usize) -> Ids {
+        return Ids {
+            hasher: RandomState::new(),
+            slots: Vec::with_capacity((agents * 2).next_power_of_two()),
+        };
+    }
+
+    /// Forgets the previous agents, and gives each of `agents` their index as id. `agents` must be distinct
+    fn reset<T: Hash>(&mut self, agents: &[&T]) {
+        self.slots.clear();
+        self.slots
+            .resize((agents.len() * 2).next_power_of_two(), NONE);
+
+        for (id, agent) in agents.iter().enumerate() {
+            let mut slot = self.first_slot(*agent);
+            while self.slots[slot] != NONE {
+                slot = (slot + 1) & (self.slots.len() - 1);
+            }
+            self.slots[slot] = id as u32;
+        }
+    }
+
+    /// The id of `agent`, if they are one of the `agents` given to `reset`
+    fn get<T: Eq + Hash>(&self, agents: &[&T], agent: &T) -> Option<u32> {
+        let mut slot = self.first_slot(agent);
+        loop {
+            let id = self.slots[slot];
+            if id == NONE {
+                return None;
+            }
+            if agents[id as usize] == agent {
+                return Some(id);
+            }
+            slot = (slot + 1) & (self.slots.len() - 1);
+        }
+    }
+
+    fn first_slot<T: Hash>(&self, agent: &T) -> usize {
+        return self.hasher.hash_one(agent) as usize & (self.slots.len() - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(buffers: &mut Buffers, men: &[Vec<u32>], women: &[Vec<u32>]) {
+        buffers.reset(men.len(), women.len());
+        for preferences in men {
+            buffers.push_man(preferences.iter().cloned());
+        }
+        for (woman, preferences) in women.iter().enumerate() {
+            buffers.set_woman(woman as u32, preferences.iter().cloned());
+        }
+    }
+
+    #[test]
+    fn test_buffers_are_reused_across_sizes() {
+        let mut buffers = Buffers::with_capacity(3, 3);
+
+        load(
+            &mut buffers,
+            &[vec![0, 1, 2], vec![1, 2, 0], vec![2, 0, 1]],
+            &[vec![1, 2, 0], vec![2, 0, 1], vec![0, 1, 2]],
+        );
+        buffers.solve();
+        let mut engagements: Vec<(u32, u32)> = buffers.engagements().collect();
+        engagements.sort();
+        assert_eq!(engagements, vec![(0, 0), (1, 1), (2, 2)]);
+
+        // Woman 1 does not accept man 1, and nobody wants woman 2
+        load(
+            &mut buffers,
+            &[vec![0, 1], vec![0, 1]],
+            &[vec![1, 0], vec![0], vec![0, 1]],
+        );
+        buffers.solve();
+        let mut engagements: Vec<(u32, u32)> = buffers.engagements().collect();
+        engagements.sort();
+        assert_eq!(engagements, vec![(0, 1), (1, 0)]);
+    }
+
+    #[test]
+    fn test_ids_find_every_agent() {
+        let names: Vec<String> = (0..50).map(|i| format!("agent {}", i)).collect();
+        let agents: Vec<&String> = names.iter().collect();
+        let mut ids = Ids::default();
+
+        ids.reset(&agents);
+        for (id, agent) in agents.iter().enumerate() {
+            assert_eq!(ids.get(&agents, *agent), Some(id as u32));
+        }
+        assert_eq!(ids.get(&agents, &"stranger".to_string()), None);
+
+        ids.reset(&agents[..0]);
+        assert_eq!(ids.get(&agents[..0], agents[0]), None);
+    }
+}
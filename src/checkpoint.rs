@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::hash::Hash;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+const HEADER: &str = "matchertools checkpoint 1";
+
+/**
+    The state of a `GaleShapleyRun` between two rounds, in a form that outlives the run
+
+    Agents are kept by value rather than by internal id, so a checkpoint written by one process can be
+    resumed by another that builds the same preferences, whatever order its `HashMap`s are in. See
+    `GaleShapleyRun::checkpoint` and `GaleShapleyRun::resume`.
+
+    On file, a checkpoint is plain text: a header line, then one tab-separated line per value. Agents
+    are written with `Display` and read back with `FromStr`.
+
+    # Examples
+    ```
+    use matchertools::{Checkpoint, GaleShapleyRun, Instance};
+
+    let mut instance = Instance::new();
+    for (man, women) in vec![("julius", ["cleopatra", "boudica"]), ("antony", ["cleopatra", "boudica"])] {
+        instance.men.insert(man.to_string(), women.iter().map(|woman| woman.to_string()).collect());
+    }
+    for (woman, men) in vec![("cleopatra", ["antony", "julius"]), ("boudica", ["julius", "antony"])] {
+        instance.women.insert(woman.to_string(), men.iter().map(|man| man.to_string()).collect());
+    }
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+
+    let mut run = GaleShapleyRun::new(&men_preferences, &women_preferences);
+    run.next();
+    let mut file = Vec::new();
+    run.checkpoint().write(&mut file).unwrap();
+
+    // Later, possibly in another process
+    let checkpoint: Checkpoint<String> = Checkpoint::read(file.as_slice()).unwrap();
+    let mut resumed = GaleShapleyRun::resume(&men_preferences, &women_preferences, &checkpoint).unwrap();
+
+    assert_eq!(resumed.next().unwrap().number, 2);
+    assert!(resumed.is_finished());
+    assert_eq!(
+        resumed.engagements(),
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+    ```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint<T: Eq + Hash> {
+    /// Number of rounds played so far
+    pub rounds_played: usize,
+    /// Number of women each man has proposed to. Men who have not proposed yet can be left out
+    pub proposals: HashMap<T, usize>,
+    /// The tentative engagements, man -> woman
+    pub engagements: HashMap<T, T>,
+}
+
+impl<T: Eq + Hash> Checkpoint<T> {
+    /// Writes the checkpoint as text. Fails with `InvalidInput` if an agent's text has a tab or a line break
+    pub fn write<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+        T: Display,
+    {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "rounds\t{}", self.rounds_played)?;
+        for (man, count) in &self.proposals {
+            writeln!(writer, "proposed\t{}\t{}", field(man)?, count)?;
+        }
+        for (man, woman) in &self.engagements {
+            writeln!(writer, "engaged\t{}\t{}", field(man)?, field(woman)?)?;
+        }
+
+        return writer.flush();
+    }
+
+    /// Reads a checkpoint written by `write`. Fails with `InvalidData`, naming the line, if it is not one
+    pub fn read<R>(reader: R) -> io::Result<Checkpoint<T>>
+    where
+        R: BufRead,
+        T: FromStr,
+    {
+        let mut checkpoint = Checkpoint {
+            rounds_played: 0,
+            proposals: HashMap::new(),
+            engagements: HashMap::new(),
+        };
+
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(invalid(1, "not a matchertools checkpoint"));
+        }
+
+        for (index, line) in lines.enumerate() {
+            let line_number = index + 2;
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+
+            match fields.as_slice() {
+                ["rounds", rounds] => {
+                    checkpoint.rounds_played = parse_count(rounds, line_number)?;
+                }
+                ["proposed", man, count] => {
+                    checkpoint.proposals.insert(
+                        parse_agent(man, line_number)?,
+                        parse_count(count, line_number)?,
+                    );
+                }
+                ["engaged", man, woman] => {
+                    checkpoint.engagements.insert(
+                        parse_agent(man, line_number)?,
+                        parse_agent(woman, line_number)?,
+                    );
+                }
+                [""] => {}
+                _ => return Err(invalid(line_number, "unexpected line")),
+            }
+        }
+
+        return Ok(checkpoint);
+    }
+}
+
+/// Why `GaleShapleyRun::resume` cannot carry on from a checkpoint with the given preferences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointError<T> {
+    /// The checkpoint has a man who is not in the men's preferences
+    UnknownMan(T),
+    /// The checkpoint has a woman who is not in the women's preferences
+    UnknownWoman(T),
+    /// The checkpoint has a man propose to more women than his list has
+    TooManyProposals {
+        /// The man who proposed too often
+        man: T,
+        /// Number of proposals the checkpoint gives him
+        proposals: usize,
+        /// Number of women on his list
+        list_length: usize,
+    },
+    /// The checkpoint has a man engaged to someone other than the last woman he proposed to
+    InconsistentEngagement {
+        /// The engaged man
+        man: T,
+        /// The woman he is engaged to
+        woman: T,
+    },
+    /// The checkpoint has a man engaged to a woman whose list does not include him
+    UnacceptableEngagement {
+        /// The engaged man
+        man: T,
+        /// The woman he is engaged to, who does not list him
+        woman: T,
+    },
+    /// The checkpoint has a woman engaged to two men
+    EngagedTwice(T),
+}
+
+impl<T: Debug> Display for CheckpointError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            CheckpointError::UnknownMan(man) => write!(f, "{:?} is not one of the men", man),
+            CheckpointError::UnknownWoman(woman) => {
+                write!(f, "{:?} is not one of the women", woman)
+            }
+            CheckpointError::TooManyProposals {
+                man,
+                proposals,
+                list_length,
+            } => write!(
+                f,
+                "{:?} made {} proposals, but only has {} women on his list",
+                man, proposals, list_length
+            ),
+            CheckpointError::InconsistentEngagement { man, woman } => write!(
+                f,
+                "{:?} is engaged to {:?}, who is not the last woman he proposed to",
+                man, woman
+            ),
+            CheckpointError::UnacceptableEngagement { man, woman } => write!(
+                f,
+                "{:?} is engaged to {:?}, who does not list him",
+                man, woman
+            ),
+            CheckpointError::EngagedTwice(woman) => {
+                write!(f, "{:?} is engaged to two men", woman)
+            }
+        };
+    }
+}
+
+impl<T: Debug> Error for CheckpointError<T> {}
+
+fn field<T: Display>(agent: &T) -> io::Result<String> {
+    let text = agent.to_string();
+    if text.contains(['\t', '\n', '\r']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("agent {:?} cannot be written to a checkpoint", text),
+        ));
+    }
+
+    return Ok(text);
+}
+
+fn parse_count(text: &str, line_number: usize) -> io::Result<usize> {
+    return text
+        .parse()
+        .map_err(|_| invalid(line_number, "expected a number"));
+}
+
+fn parse_agent<T: FromStr>(text: &str, line_number: usize) -> io::Result<T> {
+    return text
+        .parse()
+        .map_err(|_| invalid(line_number, "could not parse agent"));
+}
+
+fn invalid(line_number: usize, message: &str) -> io::Error {
+    return io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line_number, message),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GaleShapleyRun;
+
+    #[test]
+    fn test_write_then_read() {
+        let checkpoint = Checkpoint {
+            rounds_played: 4,
+            proposals: vec![(1, 3), (2, 1)].into_iter().collect(),
+            engagements: vec![(1, 7)].into_iter().collect(),
+        };
+
+        let mut file = Vec::new();
+        checkpoint.write(&mut file).unwrap();
+        assert_eq!(Checkpoint::read(file.as_slice()).unwrap(), checkpoint);
+    }
+
+    #[test]
+    fn test_read_errors_name_the_line() {
+        let error = Checkpoint::<u32>::read(
+            "matchertools checkpoint 1\nrounds\t2\nengaged\t1\tx\n".as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "line 3: could not parse agent");
+
+        let error = Checkpoint::<u32>::read("rounds\t2\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "line 1: not a matchertools checkpoint");
+
+        let checkpoint = Checkpoint {
+            rounds_played: 0,
+            proposals: HashMap::new(),
+            engagements: vec![("a\tb", "c")].into_iter().collect(),
+        };
+        let error = checkpoint.write(Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_resume_rejects_inconsistent_checkpoints() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&1, vec![&10, &11]);
+        men_preferences.insert(&2, vec![&10, &11]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&10, vec![&2, &1]);
+        women_preferences.insert(&11, vec![&1, &2]);
+
+        let resume = |proposals: Vec<(u32, usize)>, engagements: Vec<(u32, u32)>| {
+            let checkpoint = Checkpoint {
+                rounds_played: 1,
+                proposals: proposals.into_iter().collect(),
+                engagements: engagements.into_iter().collect(),
+            };
+            return GaleShapleyRun::resume(&men_preferences, &women_preferences, &checkpoint)
+                .map(|run| run.engagements().len());
+        };
+
+        assert_eq!(resume(vec![(1, 1), (2, 1)], vec![(2, 10)]), Ok(1));
+        assert_eq!(
+            resume(vec![(3, 1)], vec![]),
+            Err(CheckpointError::UnknownMan(3))
+        );
+        assert_eq!(
+            resume(vec![(1, 1)], vec![(3, 10)]),
+            Err(CheckpointError::UnknownMan(3))
+        );
+        assert_eq!(
+            resume(vec![(1, 1)], vec![(1, 12)]),
+            Err(CheckpointError::UnknownWoman(12))
+        );
+        assert_eq!(
+            resume(vec![(1, 3)], vec![]),
+            Err(CheckpointError::TooManyProposals {
+                man: 1,
+                proposals: 3,
+                list_length: 2
+            })
+        );
+        assert_eq!(
+            resume(vec![(1, 2)], vec![(1, 10)]),
+            Err(CheckpointError::InconsistentEngagement { man: 1, woman: 10 })
+        );
+        assert_eq!(
+            resume(vec![], vec![(1, 10)]),
+            Err(CheckpointError::InconsistentEngagement { man: 1, woman: 10 })
+        );
+        let error = resume(vec![(1, 1), (2, 1)], vec![(1, 10), (2, 10)]).unwrap_err();
+        assert_eq!(error, CheckpointError::EngagedTwice(10));
+        assert_eq!(error.to_string(), "10 is engaged to two men");
+
+        // 10 does not list 1, so she could never have accepted him
+        women_preferences.insert(&10, vec![&2]);
+        let checkpoint = Checkpoint {
+            rounds_played: 1,
+            proposals: vec![(1, 1)].into_iter().collect(),
+            engagements: vec![(1, 10)].into_iter().collect(),
+        };
+        let error = GaleShapleyRun::resume(&men_preferences, &women_preferences, &checkpoint)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            CheckpointError::UnacceptableEngagement { man: 1, woman: 10 }
+        );
+        assert_eq!(
+            error.to_string(),
+            "1 is engaged to 10, who does not list him"
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Preference tables as CSV, as spreadsheets save them
+//!
+//! Each side is its own file, with one row per agent: the agent, then their choices, best first.
+//!
+//! ```text
+//! julius, cleopatra, boudica
+//! antony, "cleopatra", boudica
+//! ```
+//!
+//! Fields are separated by commas and trimmed. A field can be quoted to keep commas or spaces in
+//! it, with `""` for a quote, but it cannot span several lines, so agents cannot have line breaks.
+//! Empty choices are skipped, so rows can have different lengths, and blank lines are ignored.
+//! There is no header row.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::io::{self, BufRead, Write};
+
+use crate::{Instance, Side};
+
+/// What went wrong while reading preferences
+#[derive(Debug)]
+pub enum CsvError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file could be read, but one of its lines is wrong
+    Parse {
+        /// Which side's file it is, known when reading a whole instance
+        side: Option<Side>,
+        /// The line that is wrong, counting from 1
+        line: usize,
+        /// What is wrong with it
+        message: String,
+    },
+}
+
+impl Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            CsvError::Io(error) => write!(f, "{}", error),
+            CsvError::Parse {
+                side: None,
+                line,
+                message,
+            } => write!(f, "line {}: {}", line, message),
+            CsvError::Parse {
+                side: Some(side),
+                line,
+                message,
+            } => {
+                let file = match side {
+                    Side::Men => "men's preferences",
+                    Side::Women => "women's preferences",
+                };
+                write!(f, "{}, line {}: {}", file, line, message)
+            }
+        };
+    }
+}
+
+impl Error for CsvError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return match self {
+            CsvError::Io(error) => Some(error),
+            CsvError::Parse { .. } => None,
+        };
+    }
+}
+
+impl From<io::Error> for CsvError {
+    fn from(error: io::Error) -> Self {
+        return CsvError::Io(error);
+    }
+}
+
+/**
+    Reads one side's preferences
+
+    Fails if a row cannot be parsed, if an agent has two rows, or if a row lists the same choice
+    twice.
+
+    # Examples
+    ```
+    use matchertools::csv;
+
+    let preferences = csv::read_preferences("julius, cleopatra, boudica\nantony,boudica,,\n".as_bytes()).unwrap();
+    assert_eq!(preferences["antony"], vec!["boudica"]);
+
+    let error = csv::read_preferences("julius, cleopatra\njulius, boudica\n".as_bytes()).unwrap_err();
+    assert_eq!(error.to_string(), "line 2: \"julius\" already has a row, on line 1");
+    ```
+*/
+pub fn read_preferences<R>(reader: R) -> Result<HashMap<String, Vec<String>>, CsvError>
+where
+    R: BufRead,
+{
+    return read_rows(reader).map(|rows| {
+        rows.into_iter()
+            .map(|row| (row.agent, row.choices))
+            .collect()
+    });
+}
+
+/**
+    Reads the men's and the women's preferences into an instance that `gale_shapley` accepts
+
+    On top of what `read_preferences` checks, every choice has to be an agent of the other side.
+
+    # Examples
+    ```
+    use matchertools::csv;
+
+    let men = "julius, cleopatra, boudica\nantony, cleopatra, boudica\n";
+    let women = "cleopatra, antony, julius\nboudica, julius, antony\n";
+
+    let instance = csv::read_instance(men.as_bytes(), women.as_bytes()).unwrap();
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+
+    let mut output = Vec::new();
+    csv::write_matching(&mut output, &engaged_man_woman).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "man,woman\nantony,cleopatra\njulius,boudica\n");
+    ```
+*/
+pub fn read_instance<R1, R2>(men: R1, women: R2) -> Result<Instance<String>, CsvError>
+where
+    R1: BufRead,
+    R2: BufRead,
+{
+    let on_side = |side: Side| {
+        move |error: CsvError| match error {
+            CsvError::Parse { line, message, .. } => CsvError::Parse {
+                side: Some(side),
+                line,
+                message,
+            },
+            error => error,
+        }
+    };
+    let men = read_rows(men).map_err(on_side(Side::Men))?;
+    let women = read_rows(women).map_err(on_side(Side::Women))?;
+
+    check_choices(&men, &women).map_err(on_side(Side::Men))?;
+    check_choices(&women, &men).map_err(on_side(Side::Women))?;
+
+    let to_preferences = |rows: Vec<Row>| -> HashMap<String, Vec<String>> {
+        return rows
+            .into_iter()
+            .map(|row| (row.agent, row.choices))
+            .collect();
+    };
+
+    return Ok(Instance {
+        men: to_preferences(men),
+        women: to_preferences(women),
+    });
+}
+
+/**
+    Writes a matching as CSV: a `man,woman` header, then one row per couple, sorted by man
+
+    Agents are written with `Display`, and quoted when they need to be. Fails with `InvalidInput` if
+    an agent's text has a line break, since it could not be read back.
+*/
+pub fn write_matching<W, T>(mut writer: W, engaged_man_woman: &HashMap<&T, &T>) -> io::Result<()>
+where
+    W: Write,
+    T: Display + Eq + Hash,
+{
+    let mut rows: Vec<(String, String)> = engaged_man_woman
+        .iter()
+        .map(|(man, woman)| Ok((field(*man)?, field(*woman)?)))
+        .collect::<io::Result<_>>()?;
+    rows.sort();
+
+    writeln!(writer, "man,woman")?;
+    for (man, woman) in rows {
+        writeln!(writer, "{},{}", man, woman)?;
+    }
+
+    return writer.flush();
+}
+
+/// One agent's row, with the line it was on
+struct Row {
+    line: usize,
+    agent: String,
+    choices: Vec<String>,
+}
+
+fn read_rows<R: BufRead>(reader: R) -> Result<Vec<Row>, CsvError> {
+    let mut rows: Vec<Row> = Vec::new();
+    let mut lines_of_agents: HashMap<String, usize> = HashMap::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+        // Spreadsheets like to start UTF-8 files with a byte order mark
+        let line = if index == 0 {
+            line.trim_start_matches('\u{feff}')
+        } else {
+            &line
+        };
+        let parse_error = |message: String| CsvError::Parse {
+            side: None,
+            line: line_number,
+            message,
+        };
+
+        let fields = parse_row(line).map_err(parse_error)?;
+        if fields.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+        let mut fields = fields.into_iter();
+        let agent = fields.next().unwrap();
+        if agent.is_empty() {
+            return Err(parse_error(
+                "the first field, the agent, is empty".to_string(),
+            ));
+        }
+        let fields = fields.filter(|field| !field.is_empty());
+
+        if let Some(first_line) = lines_of_agents.insert(agent.clone(), line_number) {
+            return Err(parse_error(format!(
+                "{:?} already has a row, on line {}",
+                agent, first_line
+            )));
+        }
+
+        let mut choices = Vec::new();
+        let mut seen = HashSet::new();
+        for choice in fields {
+            if !seen.insert(choice.clone()) {
+                return Err(parse_error(format!("{:?} is listed twice", choice)));
+            }
+            choices.push(choice);
+        }
+
+        rows.push(Row {
+            line: line_number,
+            agent,
+            choices,
+        });
+    }
+
+    return Ok(rows);
+}
+
+/// Fails on the first row of `rows` that lists someone who is not in `others`
+fn check_choices(rows: &[Row], others: &[Row]) -> Result<(), CsvError> {
+    let others: HashSet<&str> = others.iter().map(|row| row.agent.as_str()).collect();
+
+    for row in rows {
+        if let Some(unknown) = row
+            .choices
+            .iter()
+            .find(|choice| !others.contains(choice.as_str()))
+        {
+            return Err(CsvError::Parse {
+                side: None,
+                line: row.line,
+                message: format!("{:?} is not on the other side", unknown),
+            });
+        }
+    }
+
+    return Ok(());
+}
+
+/// Splits a line into trimmed fields, unquoting the quoted ones
+fn parse_row(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+
+        let mut field = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    None => return Err("a quoted field is not closed".to_string()),
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                }
+            }
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            fields.push(field);
+
+            match chars.next() {
+                None => return Ok(fields),
+                Some(',') => continue,
+                Some(c) => return Err(format!("unexpected {:?} after a quoted field", c)),
+            }
+        }
+
+        loop {
+            match chars.next() {
+                None => {
+                    fields.push(field.trim_end().to_string());
+                    return Ok(fields);
+                }
+                Some(',') => break,
+                Some('"') => return Err("a quote in the middle of a field".to_string()),
+                Some(c) => field.push(c),
+            }
+        }
+        fields.push(field.trim_end().to_string());
+    }
+}
+
+/// An agent as a CSV field, quoted if reading it back would otherwise change it
+fn field<T: Display>(agent: &T) -> io::Result<String> {
+    let text = agent.to_string();
+    if text.contains(['\n', '\r']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("agent {:?} has a line break", text),
+        ));
+    }
+
+    let needs_quotes = text.is_empty() || text.contains([',', '"']) || text.trim() != text;
+    if !needs_quotes {
+        return Ok(text);
+    }
+
+    return Ok(format!("\"{}\"", text.replace('"', "\"\"")));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_row() {
+        assert_eq!(parse_row("a, b ,c").unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(
+            parse_row(r#""smith, jo" , "say ""hi""",,"#).unwrap(),
+            vec!["smith, jo", r#"say "hi""#, "", ""]
+        );
+        assert_eq!(parse_row("").unwrap(), vec![""]);
+        assert!(parse_row(r#"a, "b"#).is_err());
+        assert!(parse_row(r#"a, "b" c"#).is_err());
+        assert!(parse_row(r#"a, b"c"#).is_err());
+    }
+
+    #[test]
+    fn test_read_preferences_errors() {
+        for (text, message) in [
+            ("a, b\na, c\n", "line 2: \"a\" already has a row, on line 1"),
+            (
+                " , ,\n, b, c\n",
+                "line 2: the first field, the agent, is empty",
+            ),
+            ("a, b, c, b\n", "line 1: \"b\" is listed twice"),
+            ("a, b\n\nc, \"d\n", "line 3: a quoted field is not closed"),
+        ]
+        .iter()
+        {
+            let error = read_preferences(text.as_bytes()).unwrap_err();
+            assert_eq!(error.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn test_read_instance_errors() {
+        let men = "\u{feff}julius, cleopatra\n\nantony, cleopatra, nefertiti\n";
+        for (women, message) in [
+            (
+                "cleopatra, antony, julius\n",
+                "men's preferences, line 3: \"nefertiti\" is not on the other side",
+            ),
+            (
+                "cleopatra, antony, julius\n\"boudica, julius\n",
+                "women's preferences, line 2: a quoted field is not closed",
+            ),
+        ]
+        .iter()
+        {
+            let error = read_instance(men.as_bytes(), women.as_bytes()).unwrap_err();
+            assert_eq!(error.to_string(), *message);
+        }
+    }
+
+    #[test]
+    fn test_written_fields_read_back() {
+        for agent in ["plain", "smith, jo", " padded ", r#"say "hi""#, ""].iter() {
+            assert_eq!(
+                parse_row(&field(agent).unwrap()).unwrap(),
+                vec![agent.to_string()]
+            );
+        }
+        for agent in ["two\nlines", "carriage\rreturn"].iter() {
+            assert_eq!(
+                field(agent).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+    }
+}
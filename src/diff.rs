@@ -0,0 +1,286 @@
+//! Compares two matchings of the same instance, e.g. before and after a data correction
+//!
+//! Ranks are indexed from zero, so a negative rank delta means the agent got a better partner.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::metrics;
+use crate::Side;
+
+/// How the partner of a single agent changed. `None` means unmatched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RankChange {
+    /// Rank of the partner in the `before` matching
+    pub before: Option<usize>,
+    /// Rank of the partner in the `after` matching
+    pub after: Option<usize>,
+}
+
+impl RankChange {
+    /// `after - before`, or `None` if the agent is unmatched in either matching
+    pub fn delta(&self) -> Option<i64> {
+        return match (self.before, self.after) {
+            (Some(before), Some(after)) => Some(after as i64 - before as i64),
+            _ => None,
+        };
+    }
+
+    /// Returns true if the agent is better off after the change. Any partner beats no partner
+    pub fn improved(&self) -> bool {
+        return rank_key(self.after) < rank_key(self.before);
+    }
+
+    /// Returns true if the agent is worse off after the change
+    pub fn worsened(&self) -> bool {
+        return rank_key(self.after) > rank_key(self.before);
+    }
+}
+
+/// Whether every agent of a side is at least as well off in one matching as in the other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dominance {
+    /// Nobody's rank changed
+    Same,
+    /// Nobody is worse off before, and somebody is better off before
+    BeforeDominates,
+    /// Nobody is worse off after, and somebody is better off after
+    AfterDominates,
+    /// Some agents gained and some lost
+    Incomparable,
+}
+
+/// The changes for one side of the market
+#[derive(Debug, Clone)]
+pub struct SideDiff<'a, T> {
+    /// Every agent whose partner changed
+    pub changes: HashMap<&'a T, RankChange>,
+    /// Sum of the rank deltas of agents matched in both matchings. Negative means the side is better off
+    pub total_rank_delta: i64,
+    /// Number of agents who are better off after the change
+    pub improved: usize,
+    /// Number of agents who are worse off after the change
+    pub worsened: usize,
+    /// Whether one matching is at least as good as the other for everyone on this side
+    pub dominance: Dominance,
+}
+
+/// The difference between two matchings. See `compare`
+#[derive(Debug, Clone)]
+pub struct MatchingDiff<'a, T> {
+    /// (man, woman) pairs that are only in the `before` matching
+    pub removed_pairs: Vec<(&'a T, &'a T)>,
+    /// (man, woman) pairs that are only in the `after` matching
+    pub added_pairs: Vec<(&'a T, &'a T)>,
+    /// How the men's partners changed
+    pub men: SideDiff<'a, T>,
+    /// How the women's partners changed
+    pub women: SideDiff<'a, T>,
+}
+
+impl<'a, T> MatchingDiff<'a, T> {
+    /// Returns true if both matchings are the same
+    pub fn is_empty(&self) -> bool {
+        return self.removed_pairs.is_empty() && self.added_pairs.is_empty();
+    }
+
+    /// The side whose total rank improved the most, or `None` if both changed equally
+    pub fn benefited_side(&self) -> Option<Side> {
+        if self.men.total_rank_delta < self.women.total_rank_delta {
+            return Some(Side::Men);
+        } else if self.women.total_rank_delta < self.men.total_rank_delta {
+            return Some(Side::Women);
+        }
+
+        return None;
+    }
+}
+
+/**
+    Compares two matchings of the same preferences
+
+    Both matchings map each man to a woman, just like the return value of `gale_shapley`.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::diff::{self, Dominance};
+    use matchertools::Side;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let before = matchertools::gale_shapley(&men_preferences, &women_preferences);
+    let after = matchertools::gale_shapley(&women_preferences, &men_preferences)
+        .into_iter()
+        .map(|(woman, man)| (man, woman))
+        .collect();
+
+    let diff = diff::compare(&men_preferences, &women_preferences, &before, &after);
+
+    assert_eq!(diff.added_pairs.len(), 2);
+    assert_eq!(diff.men.changes.get(&"julius").unwrap().delta(), Some(1));
+    assert_eq!(diff.men.dominance, Dominance::BeforeDominates);
+    assert_eq!(diff.women.dominance, Dominance::AfterDominates);
+    assert_eq!(diff.benefited_side(), Some(Side::Women));
+    ```
+*/
+pub fn compare<'a, T>(
+    men_preferences: &'a HashMap<&T, Vec<&T>>,
+    women_preferences: &'a HashMap<&T, Vec<&T>>,
+    before: &HashMap<&'a T, &'a T>,
+    after: &HashMap<&'a T, &'a T>,
+) -> MatchingDiff<'a, T>
+where
+    T: Eq + Hash,
+{
+    let mut removed_pairs: Vec<(&T, &T)> = Vec::new();
+    let mut added_pairs: Vec<(&T, &T)> = Vec::new();
+
+    for (man, woman) in before {
+        if after.get(man) != Some(woman) {
+            removed_pairs.push((man, woman));
+        }
+    }
+    for (man, woman) in after {
+        if before.get(man) != Some(woman) {
+            added_pairs.push((man, woman));
+        }
+    }
+
+    let metrics_before = metrics::compute(men_preferences, women_preferences, before);
+    let metrics_after = metrics::compute(men_preferences, women_preferences, after);
+
+    let mut changed_men: Vec<&T> = Vec::new();
+    let mut changed_women: Vec<&T> = Vec::new();
+    for (man, woman) in removed_pairs.iter().chain(added_pairs.iter()) {
+        changed_men.push(man);
+        changed_women.push(woman);
+    }
+
+    return MatchingDiff {
+        removed_pairs,
+        added_pairs,
+        men: compare_side(
+            &changed_men,
+            &metrics_before.men.ranks,
+            &metrics_after.men.ranks,
+        ),
+        women: compare_side(
+            &changed_women,
+            &metrics_before.women.ranks,
+            &metrics_after.women.ranks,
+        ),
+    };
+}
+
+fn compare_side<'a, T>(
+    changed_agents: &[&'a T],
+    ranks_before: &HashMap<&'a T, usize>,
+    ranks_after: &HashMap<&'a T, usize>,
+) -> SideDiff<'a, T>
+where
+    T: Eq + Hash,
+{
+    let mut changes: HashMap<&T, RankChange> = HashMap::new();
+    for agent in changed_agents {
+        changes.insert(
+            agent,
+            RankChange {
+                before: ranks_before.get(agent).cloned(),
+                after: ranks_after.get(agent).cloned(),
+            },
+        );
+    }
+
+    let total_rank_delta: i64 = changes.values().filter_map(|change| change.delta()).sum();
+    let improved = changes.values().filter(|change| change.improved()).count();
+    let worsened = changes.values().filter(|change| change.worsened()).count();
+
+    let dominance = match (improved, worsened) {
+        (0, 0) => Dominance::Same,
+        (_, 0) => Dominance::AfterDominates,
+        (0, _) => Dominance::BeforeDominates,
+        _ => Dominance::Incomparable,
+    };
+
+    return SideDiff {
+        changes,
+        total_rank_delta,
+        improved,
+        worsened,
+        dominance,
+    };
+}
+
+/// Orders ranks so that being unmatched is worse than any partner
+fn rank_key(rank: Option<usize>) -> usize {
+    return rank.unwrap_or(usize::MAX);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_change() {
+        let change = RankChange {
+            before: Some(2),
+            after: Some(0),
+        };
+        assert_eq!(change.delta(), Some(-2));
+        assert!(change.improved());
+        assert!(!change.worsened());
+
+        let change = RankChange {
+            before: Some(0),
+            after: None,
+        };
+        assert_eq!(change.delta(), None);
+        assert!(change.worsened());
+    }
+
+    #[test]
+    fn test_compare_side_incomparable() {
+        let mut ranks_before = HashMap::new();
+        ranks_before.insert(&0, 0);
+        ranks_before.insert(&1, 2);
+        ranks_before.insert(&2, 1);
+
+        let mut ranks_after = HashMap::new();
+        ranks_after.insert(&0, 1);
+        ranks_after.insert(&1, 0);
+        ranks_after.insert(&2, 1);
+
+        let side = compare_side(&[&0, &1], &ranks_before, &ranks_after);
+        assert_eq!(side.changes.len(), 2);
+        assert_eq!(side.total_rank_delta, -1);
+        assert_eq!(side.improved, 1);
+        assert_eq!(side.worsened, 1);
+        assert_eq!(side.dominance, Dominance::Incomparable);
+    }
+
+    #[test]
+    fn test_compare_same_matching() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1]);
+        men_preferences.insert(&1, vec![&1, &0]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&0, &1]);
+        women_preferences.insert(&1, vec![&1, &0]);
+
+        let matching = crate::gale_shapley(&men_preferences, &women_preferences);
+        let diff = compare(&men_preferences, &women_preferences, &matching, &matching);
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.men.dominance, Dominance::Same);
+        assert_eq!(diff.women.dominance, Dominance::Same);
+        assert_eq!(diff.benefited_side(), None);
+    }
+}
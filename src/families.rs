@@ -0,0 +1,279 @@
+//! Hand-built instances with known properties, to stress the solver
+//!
+//! Men are numbered `0..n` and women `0..n`, like in `generator`.
+
+use std::collections::HashMap;
+
+use crate::Instance;
+
+/**
+    The worst case for `gale_shapley`: it takes `n * (n - 1) + 1` proposals, the most possible
+
+    Man `i < n - 1` goes round the first `n - 1` women starting from woman `i`, and ranks woman
+    `n - 1` last. Man `n - 1` ranks the women in order. Woman `j` prefers man `j + 1`, then man
+    `n - 1`, then the others in cyclic order. Every man but one ends up with his last choice.
+
+    # Examples
+    ```
+    use matchertools::families;
+
+    let instance = families::many_proposals(6);
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let (_, statistics) =
+        matchertools::gale_shapley_with_statistics(&men_preferences, &women_preferences);
+
+    assert_eq!(statistics.proposals, 6 * 5 + 1);
+    ```
+*/
+pub fn many_proposals(n: usize) -> Instance<u32> {
+    let n = n as u32;
+    let cycle = n.saturating_sub(1);
+
+    let mut men: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut women: HashMap<u32, Vec<u32>> = HashMap::new();
+    for i in 0..cycle {
+        let mut preferences: Vec<u32> = (0..cycle).map(|k| (i + k) % cycle).collect();
+        preferences.push(n - 1);
+        men.insert(i, preferences);
+
+        let mut preferences: Vec<u32> = (0..cycle).map(|k| (i + 1 + k) % cycle).collect();
+        preferences.insert(1, n - 1);
+        women.insert(i, preferences);
+    }
+    if n > 0 {
+        men.insert(n - 1, (0..n).collect());
+        women.insert(n - 1, (0..n).collect());
+    }
+
+    return Instance { men, women };
+}
+
+/**
+    Instances with exponentially many stable matchings (Irving and Leather, 1986), with `n = 2^k`
+
+    Starting from a single couple, each step doubles the instance. Each agent has a copy; a man ranks
+    the women of his own copy first, in the old order, then the other copy; a woman ranks the men of
+    the other copy first. The number of stable matchings for `k = 0, 1, 2, 3, 4` is 1, 2, 10, 268
+    and 195472.
+
+    # Examples
+    ```
+    use matchertools::families;
+
+    let instance = families::irving_leather(2);
+    assert_eq!(instance.men.len(), 4);
+    assert_eq!(instance.men.get(&0).unwrap(), &vec![0, 1, 2, 3]);
+    assert_eq!(instance.women.get(&0).unwrap(), &vec![3, 2, 1, 0]);
+    ```
+*/
+pub fn irving_leather(k: u32) -> Instance<u32> {
+    let mut men: Vec<Vec<u32>> = vec![vec![0]];
+    let mut women: Vec<Vec<u32>> = vec![vec![0]];
+
+    for _ in 0..k {
+        let n = men.len() as u32;
+        let shifted = |preferences: &Vec<u32>| -> Vec<u32> {
+            return preferences.iter().map(|agent| agent + n).collect();
+        };
+
+        let mut doubled_men: Vec<Vec<u32>> = Vec::new();
+        let mut doubled_women: Vec<Vec<u32>> = Vec::new();
+        for preferences in &men {
+            doubled_men.push([preferences.clone(), shifted(preferences)].concat());
+        }
+        for preferences in &men {
+            doubled_men.push([shifted(preferences), preferences.clone()].concat());
+        }
+        for preferences in &women {
+            doubled_women.push([shifted(preferences), preferences.clone()].concat());
+        }
+        for preferences in &women {
+            doubled_women.push([preferences.clone(), shifted(preferences)].concat());
+        }
+
+        men = doubled_men;
+        women = doubled_women;
+    }
+
+    return Instance {
+        men: numbered(men),
+        women: numbered(women),
+    };
+}
+
+/**
+    An instance whose only stable matching pairs man `i` with woman `i`
+
+    Everyone has the same list, `0, 1, ..., n - 1`: the most popular man and woman are matched to
+    each other, then the next ones, and so on. It takes `n * (n + 1) / 2` proposals.
+
+    # Examples
+    ```
+    use matchertools::families;
+
+    let instance = families::unique_stable_matching(3);
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+
+    for (man, woman) in engaged_man_woman {
+        assert_eq!(man, woman);
+    }
+    ```
+*/
+pub fn unique_stable_matching(n: usize) -> Instance<u32> {
+    let master_list: Vec<u32> = (0..n as u32).collect();
+
+    return Instance {
+        men: (0..n as u32)
+            .map(|man| (man, master_list.clone()))
+            .collect(),
+        women: (0..n as u32)
+            .map(|woman| (woman, master_list.clone()))
+            .collect(),
+    };
+}
+
+/**
+    A stable roommates instance with no stable matching
+
+    There is a single group of `n` agents, each ranking all the others. Agents 0, 1 and 2 rank each
+    other first in a cycle: 0 prefers 1, 1 prefers 2 and 2 prefers 0. Everyone ranks 0, 1 and 2 before
+    the rest. Since `n` is even, one of the three has to be paired outside the cycle, and the agent of
+    the cycle who ranks that one first would rather be with them, and the other way round.
+
+    # Panics
+    If `n` is odd or smaller than 4.
+
+    # Examples
+    ```
+    use matchertools::families;
+
+    let preferences = families::unsolvable_roommates(4);
+    assert_eq!(preferences.get(&0).unwrap(), &vec![1, 2, 3]);
+    assert_eq!(preferences.get(&3).unwrap(), &vec![0, 1, 2]);
+    ```
+*/
+pub fn unsolvable_roommates(n: usize) -> HashMap<u32, Vec<u32>> {
+    assert!(
+        n >= 4 && n % 2 == 0,
+        "unsolvable_roommates needs an even number of agents, at least 4"
+    );
+    let n = n as u32;
+
+    let mut preferences: HashMap<u32, Vec<u32>> = HashMap::new();
+    for agent in 0..n {
+        let mut choices: Vec<u32> = match agent {
+            0 => vec![1, 2],
+            1 => vec![2, 0],
+            2 => vec![0, 1],
+            _ => vec![0, 1, 2],
+        };
+        choices.extend((3..n).filter(|other| *other != agent));
+        preferences.insert(agent, choices);
+    }
+
+    return preferences;
+}
+
+/// Agent `i` gets the `i`-th list
+fn numbered(preferences: Vec<Vec<u32>>) -> HashMap<u32, Vec<u32>> {
+    return preferences
+        .into_iter()
+        .enumerate()
+        .map(|(agent, choices)| (agent as u32, choices))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_stable_matchings(instance: &Instance<u32>) -> usize {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        return crate::brute_force::stable_matchings(&men_preferences, &women_preferences).len();
+    }
+
+    fn proposals(instance: &Instance<u32>) -> usize {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let (_, statistics) =
+            crate::gale_shapley_with_statistics(&men_preferences, &women_preferences);
+        return statistics.proposals;
+    }
+
+    #[test]
+    fn test_many_proposals() {
+        for n in 1..12 {
+            assert_eq!(proposals(&many_proposals(n)), n * (n - 1) + 1);
+        }
+        assert_eq!(proposals(&many_proposals(0)), 0);
+    }
+
+    #[test]
+    fn test_irving_leather_counts() {
+        assert_eq!(count_stable_matchings(&irving_leather(0)), 1);
+        assert_eq!(count_stable_matchings(&irving_leather(1)), 2);
+        assert_eq!(count_stable_matchings(&irving_leather(2)), 10);
+        assert_eq!(count_stable_matchings(&irving_leather(3)), 268);
+    }
+
+    #[test]
+    fn test_unique_stable_matching() {
+        for n in 1..7 {
+            let instance = unique_stable_matching(n);
+            assert_eq!(count_stable_matchings(&instance), 1);
+            assert_eq!(proposals(&instance), n * (n + 1) / 2);
+        }
+    }
+
+    #[test]
+    fn test_unsolvable_roommates() {
+        for n in [4, 6, 8].iter() {
+            let preferences = unsolvable_roommates(*n);
+            let rank = |agent: u32, other: u32| {
+                preferences
+                    .get(&agent)
+                    .unwrap()
+                    .iter()
+                    .position(|a| *a == other)
+                    .unwrap()
+            };
+
+            // Every perfect matching, built by pairing the lowest unpaired agent with someone
+            let mut partial: Vec<Vec<Option<u32>>> = vec![vec![None; *n]];
+            let mut matchings: Vec<Vec<u32>> = Vec::new();
+            while let Some(partners) = partial.pop() {
+                let first = match partners.iter().position(Option::is_none) {
+                    Some(first) => first,
+                    None => {
+                        matchings.push(partners.iter().map(|p| p.unwrap()).collect());
+                        continue;
+                    }
+                };
+                for other in first + 1..*n {
+                    if partners[other].is_none() {
+                        let mut next = partners.clone();
+                        next[first] = Some(other as u32);
+                        next[other] = Some(first as u32);
+                        partial.push(next);
+                    }
+                }
+            }
+
+            for partners in matchings {
+                let blocked = (0..*n as u32).any(|a| {
+                    (0..*n as u32).any(|b| {
+                        a != b
+                            && partners[a as usize] != b
+                            && rank(a, b) < rank(a, partners[a as usize])
+                            && rank(b, a) < rank(b, partners[b as usize])
+                    })
+                });
+                assert!(blocked);
+            }
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//! Seeded random instances, for testing and experiments
+//!
+//! Men are numbered `0..men` and women `0..women`. The same arguments always give the same instance,
+//! on every platform: the random numbers come from a small built-in generator, not from a crate.
+
+use std::collections::HashMap;
+
+use crate::Instance;
+
+/**
+    Every man ranks every woman and every woman ranks every man, uniformly at random
+
+    # Examples
+    ```
+    use matchertools::generator;
+
+    let instance = generator::uniform(3, 4, 42);
+    assert_eq!(instance.men.len(), 3);
+    assert_eq!(instance.women.len(), 4);
+    assert_eq!(instance.men.get(&0).unwrap().len(), 4);
+    assert_eq!(instance, generator::uniform(3, 4, 42));
+    ```
+*/
+pub fn uniform(men: usize, women: usize, seed: u64) -> Instance<u32> {
+    let mut rng = Rng::new(seed);
+
+    return Instance {
+        men: (0..men)
+            .map(|man| (man as u32, shuffled(women, &mut rng)))
+            .collect(),
+        women: (0..women)
+            .map(|woman| (woman as u32, shuffled(men, &mut rng)))
+            .collect(),
+    };
+}
+
+/**
+    Like `uniform`, but each man and woman find each other acceptable only with probability `density`
+
+    Acceptability is mutual: a woman is on a man's list exactly when he is on hers. Some agents may
+    end up with empty lists.
+
+    # Examples
+    ```
+    use matchertools::generator;
+
+    let instance = generator::incomplete(10, 10, 0.3, 7);
+    for (man, women) in &instance.men {
+        for woman in women {
+            assert!(instance.women.get(woman).unwrap().contains(man));
+        }
+    }
+    ```
+*/
+pub fn incomplete(men: usize, women: usize, density: f64, seed: u64) -> Instance<u32> {
+    let mut rng = Rng::new(seed);
+
+    let mut men_preferences: Vec<Vec<u32>> = vec![Vec::new(); men];
+    let mut women_preferences: Vec<Vec<u32>> = vec![Vec::new(); women];
+    for (man, man_preferences) in men_preferences.iter_mut().enumerate() {
+        for (woman, woman_preferences) in women_preferences.iter_mut().enumerate() {
+            if rng.next_f64() < density {
+                man_preferences.push(woman as u32);
+                woman_preferences.push(man as u32);
+            }
+        }
+    }
+
+    for preferences in men_preferences
+        .iter_mut()
+        .chain(women_preferences.iter_mut())
+    {
+        rng.shuffle(preferences);
+    }
+
+    return Instance {
+        men: numbered(men_preferences),
+        women: numbered(women_preferences),
+    };
+}
+
+/**
+    Correlated preferences: everyone starts from the same master list, disturbed by `noise`
+
+    The master list ranks agents by number, so man 0 and woman 0 are the most popular. Each agent
+    scores every candidate as `(1 - noise) * master_position + noise * random` and ranks them by score.
+    `noise` 0 gives everyone the master list, 1 gives `uniform`.
+
+    # Examples
+    ```
+    use matchertools::generator;
+
+    let instance = generator::master_list(4, 4, 0.0, 1);
+    assert_eq!(instance.men.get(&3).unwrap(), &vec![0, 1, 2, 3]);
+    assert_eq!(instance.women.get(&0).unwrap(), &vec![0, 1, 2, 3]);
+    ```
+*/
+pub fn master_list(men: usize, women: usize, noise: f64, seed: u64) -> Instance<u32> {
+    let mut rng = Rng::new(seed);
+
+    let ranking = |candidates: usize, rng: &mut Rng| {
+        let scores: Vec<f64> = (0..candidates)
+            .map(|position| {
+                (1.0 - noise) * position as f64 / candidates as f64 + noise * rng.next_f64()
+            })
+            .collect();
+        return sorted_by_score(&scores);
+    };
+
+    return Instance {
+        men: (0..men)
+            .map(|man| (man as u32, ranking(women, &mut rng)))
+            .collect(),
+        women: (0..women)
+            .map(|woman| (woman as u32, ranking(men, &mut rng)))
+            .collect(),
+    };
+}
+
+/**
+    Preferences drawn from the Mallows model around the master list `0, 1, 2, ...`
+
+    A list that needs `k` swaps of adjacent entries to become the master list has probability
+    proportional to `dispersion^k`. `dispersion` 0 gives everyone the master list, 1 gives `uniform`.
+    Lists are sampled with the repeated insertion method.
+
+    # Examples
+    ```
+    use matchertools::generator;
+
+    let instance = generator::mallows(3, 5, 0.0, 9);
+    assert_eq!(instance.men.get(&2).unwrap(), &vec![0, 1, 2, 3, 4]);
+
+    let instance = generator::mallows(3, 5, 0.5, 9);
+    assert_eq!(instance.women.len(), 5);
+    ```
+*/
+pub fn mallows(men: usize, women: usize, dispersion: f64, seed: u64) -> Instance<u32> {
+    let mut rng = Rng::new(seed);
+
+    return Instance {
+        men: (0..men)
+            .map(|man| (man as u32, mallows_ranking(women, dispersion, &mut rng)))
+            .collect(),
+        women: (0..women)
+            .map(|woman| (woman as u32, mallows_ranking(men, dispersion, &mut rng)))
+            .collect(),
+    };
+}
+
+/**
+    Geometric preferences: every agent is a random point in the unit square and prefers the
+    agents of the other side that are closest to them
+
+    # Examples
+    ```
+    use matchertools::generator;
+
+    let instance = generator::euclidean(5, 5, 3);
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+    assert_eq!(engaged_man_woman.len(), 5);
+    ```
+*/
+pub fn euclidean(men: usize, women: usize, seed: u64) -> Instance<u32> {
+    let mut rng = Rng::new(seed);
+
+    let men_points: Vec<(f64, f64)> = (0..men).map(|_| rng.next_point()).collect();
+    let women_points: Vec<(f64, f64)> = (0..women).map(|_| rng.next_point()).collect();
+
+    let ranking = |from: (f64, f64), candidates: &[(f64, f64)]| {
+        let distances: Vec<f64> = candidates
+            .iter()
+            .map(|to| (from.0 - to.0).powi(2) + (from.1 - to.1).powi(2))
+            .collect();
+        return sorted_by_score(&distances);
+    };
+
+    return Instance {
+        men: men_points
+            .iter()
+            .enumerate()
+            .map(|(man, point)| (man as u32, ranking(*point, &women_points)))
+            .collect(),
+        women: women_points
+            .iter()
+            .enumerate()
+            .map(|(woman, point)| (woman as u32, ranking(*point, &men_points)))
+            .collect(),
+    };
+}
+
+/// Agent `i` gets the `i`-th list
+fn numbered(preferences: Vec<Vec<u32>>) -> HashMap<u32, Vec<u32>> {
+    return preferences
+        .into_iter()
+        .enumerate()
+        .map(|(agent, choices)| (agent as u32, choices))
+        .collect();
+}
+
+/// `0..candidates` in random order
+fn shuffled(candidates: usize, rng: &mut Rng) -> Vec<u32> {
+    let mut ranking: Vec<u32> = (0..candidates as u32).collect();
+    rng.shuffle(&mut ranking);
+    return ranking;
+}
+
+/// Candidates by increasing score. Ties go to the lower number
+fn sorted_by_score(scores: &[f64]) -> Vec<u32> {
+    let mut ranking: Vec<u32> = (0..scores.len() as u32).collect();
+    ranking.sort_by(|a, b| {
+        scores[*a as usize]
+            .partial_cmp(&scores[*b as usize])
+            .unwrap()
+            .then(a.cmp(b))
+    });
+    return ranking;
+}
+
+/// Repeated insertion: candidate `i` goes `j` places before the end of the first `i` candidates with
+/// probability proportional to `dispersion^j`
+fn mallows_ranking(candidates: usize, dispersion: f64, rng: &mut Rng) -> Vec<u32> {
+    let mut ranking: Vec<u32> = Vec::with_capacity(candidates);
+
+    for candidate in 0..candidates {
+        let weights: Vec<f64> = (0..=candidate).map(|j| dispersion.powi(j as i32)).collect();
+        let mut target = rng.next_f64() * weights.iter().sum::<f64>();
+        let mut places_before_end = candidate;
+        for (j, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                places_before_end = j;
+                break;
+            }
+            target -= weight;
+        }
+        ranking.insert(candidate - places_before_end, candidate as u32);
+    }
+
+    return ranking;
+}
+
+/// SplitMix64. Small, fast and good enough for generating test instances
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        return Rng { state: seed };
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        return z ^ (z >> 31);
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        return (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    }
+
+    /// Uniform in `0..bound`
+    fn next_below(&mut self, bound: usize) -> usize {
+        return (self.next_f64() * bound as f64) as usize;
+    }
+
+    fn next_point(&mut self) -> (f64, f64) {
+        return (self.next_f64(), self.next_f64());
+    }
+
+    /// Fisher-Yates
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.next_below(i + 1);
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_permutation(ranking: &[u32], candidates: usize) -> bool {
+        let mut sorted = ranking.to_vec();
+        sorted.sort_unstable();
+        return sorted == (0..candidates as u32).collect::<Vec<u32>>();
+    }
+
+    #[test]
+    fn test_complete_generators_give_permutations() {
+        let instances = vec![
+            uniform(6, 4, 1),
+            master_list(6, 4, 0.3, 1),
+            mallows(6, 4, 0.7, 1),
+            euclidean(6, 4, 1),
+        ];
+
+        for instance in instances {
+            assert_eq!(instance.men.len(), 6);
+            assert_eq!(instance.women.len(), 4);
+            assert!(instance
+                .men
+                .values()
+                .all(|ranking| is_permutation(ranking, 4)));
+            assert!(instance
+                .women
+                .values()
+                .all(|ranking| is_permutation(ranking, 6)));
+        }
+    }
+
+    #[test]
+    fn test_seed_changes_instance() {
+        assert_eq!(uniform(8, 8, 5), uniform(8, 8, 5));
+        assert_eq!(incomplete(8, 8, 0.5, 5), incomplete(8, 8, 0.5, 5));
+        assert_ne!(uniform(8, 8, 5), uniform(8, 8, 6));
+    }
+
+    #[test]
+    fn test_incomplete_density() {
+        assert!(incomplete(5, 5, 0.0, 3).men.values().all(Vec::is_empty));
+        assert_eq!(incomplete(5, 5, 1.0, 3).women.get(&2).unwrap().len(), 5);
+
+        let instance = incomplete(40, 40, 0.25, 3);
+        let pairs: usize = instance.men.values().map(Vec::len).sum();
+        assert!(pairs > 300 && pairs < 500);
+    }
+
+    #[test]
+    fn test_mallows_dispersion() {
+        let mut rng = Rng::new(11);
+        assert_eq!(mallows_ranking(5, 0.0, &mut rng), vec![0, 1, 2, 3, 4]);
+
+        // With a small dispersion, lists stay close to the master list
+        let inversions = |ranking: &[u32]| {
+            let mut count = 0;
+            for i in 0..ranking.len() {
+                for j in i + 1..ranking.len() {
+                    if ranking[i] > ranking[j] {
+                        count += 1;
+                    }
+                }
+            }
+            return count;
+        };
+        let close: usize = (0..50)
+            .map(|_| inversions(&mallows_ranking(10, 0.2, &mut rng)))
+            .sum();
+        let far: usize = (0..50)
+            .map(|_| inversions(&mallows_ranking(10, 1.0, &mut rng)))
+            .sum();
+        assert!(close * 4 < far);
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/**
+    A stable marriage instance that owns its agents
+
+    The functions of this crate borrow their input, so they cannot be handed preferences that were
+    built on the fly. `Instance` keeps the preferences alive and lends them out in the borrowed form.
+
+    # Examples
+    ```
+    use matchertools::Instance;
+
+    let mut instance = Instance::new();
+    instance.men.insert("julius".to_string(), vec!["cleopatra".to_string()]);
+    instance.women.insert("cleopatra".to_string(), vec!["julius".to_string()]);
+
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+
+    assert_eq!(engaged_man_woman.get(&"julius".to_string()).unwrap().as_str(), "cleopatra");
+    ```
+
+    # Serialization
+    With the `serde` feature, an instance is an object with the two sides' lists, each an object from
+    agent to its choices, best first:
+    ```json
+    {
+        "men": { "julius": ["cleopatra", "boudica"], "antony": ["cleopatra"] },
+        "women": { "cleopatra": ["antony", "julius"], "boudica": ["julius"] }
+    }
+    ```
+    Both fields are required and no other field is accepted. Every agent is matched with at most one
+    other, so there are no capacities to store. This schema only ever grows through new optional fields.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields)
+)]
+pub struct Instance<T: Eq + Hash> {
+    /// Each man's preferences, best first
+    pub men: HashMap<T, Vec<T>>,
+    /// Each woman's preferences, best first
+    pub women: HashMap<T, Vec<T>>,
+}
+
+impl<T: Eq + Hash> Default for Instance<T> {
+    fn default() -> Self {
+        return Instance {
+            men: HashMap::new(),
+            women: HashMap::new(),
+        };
+    }
+}
+
+impl<T: Eq + Hash> Instance<T> {
+    /// An instance without agents
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Copies borrowed preferences into an instance
+    pub fn from_preferences(
+        men_preferences: &HashMap<&T, Vec<&T>>,
+        women_preferences: &HashMap<&T, Vec<&T>>,
+    ) -> Self
+    where
+        T: Clone,
+    {
+        return Instance {
+            men: to_owned(men_preferences),
+            women: to_owned(women_preferences),
+        };
+    }
+
+    /// The men's preferences, in the form the rest of the crate takes
+    pub fn men_preferences(&self) -> HashMap<&T, Vec<&T>> {
+        return to_borrowed(&self.men);
+    }
+
+    /// The women's preferences, in the form the rest of the crate takes
+    pub fn women_preferences(&self) -> HashMap<&T, Vec<&T>> {
+        return to_borrowed(&self.women);
+    }
+}
+
+/**
+    A matching that owns its agents, man -> woman
+
+    `gale_shapley` and the other solvers return a matching that borrows from their input. `Matching`
+    keeps a copy that can be stored, compared or, with the `serde` feature, serialized.
+
+    # Examples
+    ```
+    use matchertools::{generator, Matching};
+
+    let instance = generator::uniform(3, 3, 1);
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+
+    let matching = Matching::from_engagements(&engaged_man_woman);
+    assert_eq!(matching.engagements(), engaged_man_woman);
+    ```
+
+    # Serialization
+    With the `serde` feature, a matching is an object from each matched man to his partner, such as
+    `{ "julius": "boudica", "antony": "cleopatra" }`. Single agents are left out.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(transparent))]
+pub struct Matching<T: Eq + Hash> {
+    /// Each matched man and his partner
+    pub engaged_man_woman: HashMap<T, T>,
+}
+
+impl<T: Eq + Hash> Matching<T> {
+    /// Copies a matching returned by one of the solvers
+    pub fn from_engagements(engaged_man_woman: &HashMap<&T, &T>) -> Self
+    where
+        T: Clone,
+    {
+        return Matching {
+            engaged_man_woman: engaged_man_woman
+                .iter()
+                .map(|(man, woman)| ((*man).clone(), (*woman).clone()))
+                .collect(),
+        };
+    }
+
+    /// The matching in the form the solvers return it
+    pub fn engagements(&self) -> HashMap<&T, &T> {
+        return self.engaged_man_woman.iter().collect();
+    }
+}
+
+fn to_owned<T: Clone + Eq + Hash>(preferences: &HashMap<&T, Vec<&T>>) -> HashMap<T, Vec<T>> {
+    return preferences
+        .iter()
+        .map(|(agent, choices)| {
+            (
+                (*agent).clone(),
+                choices.iter().map(|choice| (*choice).clone()).collect(),
+            )
+        })
+        .collect();
+}
+
+fn to_borrowed<T: Eq + Hash>(preferences: &HashMap<T, Vec<T>>) -> HashMap<&T, Vec<&T>> {
+    return preferences
+        .iter()
+        .map(|(agent, choices)| (agent, choices.iter().collect()))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_through_borrowed_preferences() {
+        let mut instance = Instance::new();
+        instance.men.insert(0, vec![0, 1]);
+        instance.men.insert(1, vec![1]);
+        instance.women.insert(0, vec![1, 0]);
+        instance.women.insert(1, vec![0, 1]);
+
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        assert_eq!(men_preferences.get(&1), Some(&vec![&1]));
+
+        assert_eq!(
+            Instance::from_preferences(&men_preferences, &women_preferences),
+            instance
+        );
+    }
+}
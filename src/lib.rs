@@ -1,6 +1,59 @@
+// The crate writes explicit returns throughout
+#![allow(clippy::needless_return)]
+
 use std::cmp::Eq;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+#[cfg(feature = "parallel")]
+use std::thread;
+
+#[cfg(feature = "parallel")]
+mod batch;
+pub mod brute_force;
+mod buffers;
+mod checkpoint;
+pub mod csv;
+pub mod diff;
+pub mod families;
+pub mod generator;
+mod instance;
+mod limits;
+pub mod manipulation;
+mod market;
+mod master_list;
+pub mod metrics;
+mod observer;
+mod preferences;
+mod rotations;
+mod run;
+mod scoring;
+pub mod sensitivity;
+mod sequential;
+mod solver;
+mod statistics;
+mod warm_start;
+
+pub use checkpoint::{Checkpoint, CheckpointError};
+pub use instance::{Instance, Matching};
+pub use limits::{CancellationToken, Interrupted, Interruption, Limits};
+pub use market::{Market, MarketUpdate, PartnerChange};
+pub use master_list::MasterList;
+pub use observer::Observer;
+pub use preferences::{Comparator, Preferences, Streams};
+pub use rotations::closest_stable_matching;
+pub use run::{GaleShapleyRun, Round};
+pub use scoring::Scoring;
+pub use solver::{Algorithm, Solver};
+pub use statistics::Statistics;
+
+/// One of the two sides of the market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    /// The side that proposes. The keys of the first argument to `gale_shapley`
+    Men,
+    /// The side that receives proposals. The keys of the second argument to `gale_shapley`
+    Women,
+}
 
 /**
     Returns a HashMap indicating who is engaged to whom using the Gale-Shapley algorithm
@@ -9,10 +62,12 @@ use std::hash::Hash;
     original stable marriage problem.
 
     # Remarks:
-    The number of men and women should be equal. In other words, `input_men_preferences` and `input_women_preferences` should have the
-    same number of keys. Also, each 'man' in `input_men_preferences` should indicate the 'ranking' of each woman in the associated vec.
+    Each 'man' in `input_men_preferences` should indicate the 'ranking' of the women in the associated vec.
     Same holds true for women - man: each woman in `input_women_preferences` should indicate her preference in the associated vec.
 
+    The lists do not have to be complete. Leaving someone out of a list means that person is unacceptable, and the two will
+    never be engaged. In that case (or when the number of men and women differ) some people may end up without a partner.
+
     # Arguments:
     * input_men_preferences - HashMap of each men to a vec of women, ordered by preference. The most preferred woman comes first in the vec
     * input_women_preferences - HashMap of each woman to a vec of men, ordered by preference. The most preferred man comes first in the vec
@@ -48,103 +103,357 @@ where
     T: Eq + Hash,
 {
     // TODO: Add validations for the input
-    let mut men_preferences: HashMap<u32, Vec<u32>> = HashMap::new();
-    let mut women_preferences: HashMap<u32, Vec<u32>> = HashMap::new();
-    let mut engagements: HashMap<&T, &T> = HashMap::new();
-    let mut men_reference_to_u32: HashMap<&T, u32> = HashMap::new();
-    let mut women_reference_to_u32: HashMap<&T, u32> = HashMap::new();
-
-    // I initially implemented the algorithm over u32. So I'm now trying to convert HashMap<T, Vec<T>> to HashMap<u32, Vec<u32>>.
-    // TODO: Get rid of this step. Rewrite the implementation to directly work on generic types
-    for (idx, man) in input_men_preferences.keys().enumerate() {
-        men_reference_to_u32.insert(man, idx as u32);
-    }
+    return Solver::new().solve(input_men_preferences, input_women_preferences);
+}
+
+/**
+    Same as `gale_shapley`, but reports every proposal, acceptance, rejection and broken engagement to `observer`
+
+    The events of a round are reported once the round is over: first the proposals, then the broken
+    engagements, then the accepted and finally the rejected proposals.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::Observer;
 
-    for (idx, woman) in input_women_preferences.keys().enumerate() {
-        women_reference_to_u32.insert(woman, idx as u32);
+    struct AuditLog {
+        lines: Vec<String>,
     }
 
-    for (man, women) in input_men_preferences.iter() {
-        let mut women_as_u32: Vec<u32> = Vec::new();
-        for woman in women {
-            women_as_u32.push(*women_reference_to_u32.get(woman).unwrap());
+    impl Observer<&'static str> for AuditLog {
+        fn on_accept(&mut self, man: &&str, woman: &&str) {
+            self.lines.push(format!("{} is engaged to {}", man, woman));
         }
-        men_preferences.insert(*men_reference_to_u32.get(man).unwrap(), women_as_u32);
-    }
 
-    for (woman, men) in input_women_preferences.iter() {
-        let mut men_as_u32: Vec<u32> = Vec::new();
-        for man in men {
-            men_as_u32.push(*men_reference_to_u32.get(man).unwrap());
+        fn on_break_engagement(&mut self, man: &&str, woman: &&str) {
+            self.lines.push(format!("{} left {}", woman, man));
         }
-        women_preferences.insert(*women_reference_to_u32.get(woman).unwrap(), men_as_u32);
     }
 
-    // men_preferences and women_preferences is HashMap<u32, Vec<u32>>
-    let engagements_u32 = gale_shapley_internal(&men_preferences, &women_preferences);
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
 
-    // convert the resulting HashMap<u32, u32> to HashMap<T, Vec<T>>
-    for (man_u32, woman_u32) in engagements_u32 {
-        let man = get_reference_from_u32(&men_reference_to_u32, man_u32).unwrap();
-        let woman = get_reference_from_u32(&women_reference_to_u32, woman_u32).unwrap();
-        engagements.insert(man, woman);
-    }
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let mut audit_log = AuditLog { lines: Vec::new() };
+    let engaged_man_woman =
+        matchertools::gale_shapley_with_observer(&men_preferences, &women_preferences, &mut audit_log);
 
-    return engagements;
+    assert_eq!(engaged_man_woman.get(&&"julius"), Some(&&"cleopatra"));
+    assert_eq!(audit_log.lines.len(), 2);
+    ```
+*/
+pub fn gale_shapley_with_observer<'a, T, O>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    observer: &mut O,
+) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+    O: Observer<T>,
+{
+    return Solver::new().solve_with_observer(
+        input_men_preferences,
+        input_women_preferences,
+        observer,
+    );
 }
 
-fn get_reference_from_u32<'a, T>(references: &HashMap<&'a T, u32>, value: u32) -> Option<&'a T>
+/**
+    Same as `gale_shapley`, but also returns statistics about the run
+
+    The statistics count the rounds, proposals, rejections and broken engagements, and time the
+    three phases of a solve: converting the input, running the algorithm and converting the result back.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let (engaged_man_woman, statistics) =
+        matchertools::gale_shapley_with_statistics(&men_preferences, &women_preferences);
+
+    assert_eq!(engaged_man_woman.get(&&"julius"), Some(&&"boudica"));
+    assert_eq!(statistics.rounds, 2);
+    assert_eq!(statistics.proposals, 3);
+    assert_eq!(statistics.rejections, 1);
+    assert_eq!(statistics.broken_engagements, 0);
+    ```
+*/
+pub fn gale_shapley_with_statistics<'a, T>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+) -> (HashMap<&'a T, &'a T>, Statistics)
 where
     T: Eq + Hash,
 {
-    for (reference, val_u32) in references {
-        if *val_u32 == value {
-            return Some(reference);
+    return Solver::new().solve_with_statistics(input_men_preferences, input_women_preferences);
+}
+
+/**
+    Returns a stable matching, starting from a previous matching instead of from scratch
+
+    Useful when the preferences changed a little since `previous_engagements` was computed. Pairs that
+    are no longer acceptable, and men who would now rather be with a woman who would have them, are
+    unmatched. Everyone else keeps their partner and the algorithm carries on from there. When most of
+    the previous matching is still stable this takes far fewer proposals than `gale_shapley`.
+
+    The result is stable, but it is not necessarily the man-optimal matching that `gale_shapley`
+    would return: it stays close to `previous_engagements` instead.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let previous = matchertools::gale_shapley(&men_preferences, &women_preferences);
+    assert_eq!(previous.get(&&"antony"), Some(&&"cleopatra"));
+
+    // Cleopatra no longer wants Antony
+    let mut new_women_preferences = women_preferences.clone();
+    new_women_preferences.insert(&"cleopatra", vec![&"julius"]);
+
+    let engaged_man_woman =
+        matchertools::gale_shapley_from(&men_preferences, &new_women_preferences, &previous);
+
+    assert_eq!(engaged_man_woman.get(&&"julius"), Some(&&"cleopatra"));
+    assert_eq!(engaged_man_woman.get(&&"antony"), Some(&&"boudica"));
+    ```
+*/
+pub fn gale_shapley_from<'a, T>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    previous_engagements: &HashMap<&T, &T>,
+) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+{
+    return Solver::new().solve_from(
+        input_men_preferences,
+        input_women_preferences,
+        previous_engagements,
+    );
+}
+
+/// The input preferences, converted to u32 so that the algorithm does not have to care about `T`
+struct Interned<'a, T> {
+    men: Vec<&'a T>,
+    women: Vec<&'a T>,
+    men_reference_to_u32: HashMap<&'a T, u32>,
+    women_reference_to_u32: HashMap<&'a T, u32>,
+    men_preferences: HashMap<u32, Vec<u32>>,
+    women_preferences: HashMap<u32, Vec<u32>>,
+}
+
+impl<'a, T> Interned<'a, T>
+where
+    T: Eq + Hash,
+{
+    fn new(
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    ) -> Interned<'a, T> {
+        let mut men_preferences: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut women_preferences: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut men_reference_to_u32: HashMap<&T, u32> = HashMap::new();
+        let mut women_reference_to_u32: HashMap<&T, u32> = HashMap::new();
+        let mut men: Vec<&T> = Vec::new();
+        let mut women: Vec<&T> = Vec::new();
+
+        // I initially implemented the algorithm over u32. So I'm now trying to convert HashMap<T, Vec<T>> to HashMap<u32, Vec<u32>>.
+        // TODO: Get rid of this step. Rewrite the implementation to directly work on generic types
+        for (idx, man) in input_men_preferences.keys().enumerate() {
+            men_reference_to_u32.insert(man, idx as u32);
+            men.push(man);
+        }
+
+        for (idx, woman) in input_women_preferences.keys().enumerate() {
+            women_reference_to_u32.insert(woman, idx as u32);
+            women.push(woman);
+        }
+
+        for (man, women) in input_men_preferences.iter() {
+            let mut women_as_u32: Vec<u32> = Vec::new();
+            for woman in women {
+                women_as_u32.push(*women_reference_to_u32.get(woman).unwrap());
+            }
+            men_preferences.insert(*men_reference_to_u32.get(man).unwrap(), women_as_u32);
         }
+
+        for (woman, men) in input_women_preferences.iter() {
+            let mut men_as_u32: Vec<u32> = Vec::new();
+            for man in men {
+                men_as_u32.push(*men_reference_to_u32.get(man).unwrap());
+            }
+            women_preferences.insert(*women_reference_to_u32.get(woman).unwrap(), men_as_u32);
+        }
+
+        return Interned {
+            men,
+            women,
+            men_reference_to_u32,
+            women_reference_to_u32,
+            men_preferences,
+            women_preferences,
+        };
     }
 
-    return None;
+    fn man(&self, man: u32) -> &'a T {
+        return self.men[man as usize];
+    }
+
+    fn woman(&self, woman: u32) -> &'a T {
+        return self.women[woman as usize];
+    }
+
+    /// convert a HashMap<u32, u32> back to HashMap<T, T>
+    fn engagements_from_u32(&self, engagements_u32: &HashMap<u32, u32>) -> HashMap<&'a T, &'a T> {
+        let mut engagements: HashMap<&T, &T> = HashMap::new();
+        for (man_u32, woman_u32) in engagements_u32 {
+            engagements.insert(self.man(*man_u32), self.woman(*woman_u32));
+        }
+
+        return engagements;
+    }
+
+    /// convert a HashMap<T, T> to HashMap<u32, u32>. Pairs with someone who is not in the input are dropped
+    fn engagements_to_u32(&self, engagements: &HashMap<&T, &T>) -> HashMap<u32, u32> {
+        let mut engagements_u32: HashMap<u32, u32> = HashMap::new();
+        for (man, woman) in engagements {
+            let man_u32 = self.men_reference_to_u32.get(man);
+            let woman_u32 = self.women_reference_to_u32.get(woman);
+            if let (Some(man_u32), Some(woman_u32)) = (man_u32, woman_u32) {
+                engagements_u32.insert(*man_u32, *woman_u32);
+            }
+        }
+
+        return engagements_u32;
+    }
+
+    fn notify<O>(&self, outcome: &RoundOutcome, observer: &mut O)
+    where
+        O: Observer<T>,
+    {
+        for (woman, men) in &outcome.proposals {
+            for man in men {
+                observer.on_proposal(self.man(*man), self.woman(*woman));
+            }
+        }
+        for (man, woman) in &outcome.broken {
+            observer.on_break_engagement(self.man(*man), self.woman(*woman));
+        }
+        for (man, woman) in &outcome.accepted {
+            observer.on_accept(self.man(*man), self.woman(*woman));
+        }
+        for (man, woman) in &outcome.rejected {
+            observer.on_reject(self.man(*man), self.woman(*woman));
+        }
+    }
 }
 
+/// Called with what happened in every round. Without one, rounds do not keep track of what happened in them
+type OnRound<'f> = Option<&'f mut dyn FnMut(&RoundOutcome)>;
+
 fn gale_shapley_internal(
     men_preferences: &HashMap<u32, Vec<u32>>,
     women_preferences: &HashMap<u32, Vec<u32>>,
+    on_round: OnRound,
 ) -> HashMap<u32, u32> {
-    /// You better go read the algorithm on wikipedia: https://en.wikipedia.org/wiki/Stable_marriage_problem
+    // You better go read the algorithm on wikipedia: https://en.wikipedia.org/wiki/Stable_marriage_problem
     // ranks are indexed from zero
     // TODO: Add validations for input
-    let mut men_preferences = men_preferences.clone();
-    let mut engaged_man_woman: HashMap<u32, u32> = HashMap::new();
+    return continue_gale_shapley_internal(
+        men_preferences.clone(),
+        women_preferences,
+        HashMap::new(),
+        1,
+        on_round,
+    );
+}
 
-    while get_unengaged_men(&men_preferences, &engaged_man_woman).len() != 0 {
-        play_round(
+/// Plays rounds until every man is engaged or has been rejected by every woman on his list
+///
+/// `men_preferences` must only hold the women each man has not proposed to yet, and
+/// `engaged_man_woman` the engagements to start from. See `accept_or_reject_proposals` for `threads`
+fn continue_gale_shapley_internal(
+    mut men_preferences: HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    mut engaged_man_woman: HashMap<u32, u32>,
+    threads: usize,
+    mut on_round: OnRound,
+) -> HashMap<u32, u32> {
+    while !get_unengaged_men(&men_preferences, &engaged_man_woman).is_empty() {
+        let outcome = play_round(
             &mut men_preferences,
-            &women_preferences,
+            women_preferences,
             &mut engaged_man_woman,
+            threads,
+            on_round.is_some(),
         );
+        if let Some(on_round) = &mut on_round {
+            on_round(&outcome);
+        }
     }
 
     return engaged_man_woman;
 }
 
+/// What happened in a single call to `play_round`. Only `proposals` is filled in unless the round is recorded
+#[derive(Default)]
+struct RoundOutcome {
+    /// woman -> the men who proposed to her
+    proposals: HashMap<u32, HashSet<u32>>,
+    /// (man, woman) pairs that got (tentatively) engaged in this round
+    accepted: Vec<(u32, u32)>,
+    /// (man, woman) pairs where the woman turned the man down in this round
+    rejected: Vec<(u32, u32)>,
+    /// (man, woman) pairs whose engagement was broken because the woman got a better proposal
+    broken: Vec<(u32, u32)>,
+}
+
 fn play_round(
-    mut men_preferences: &mut HashMap<u32, Vec<u32>>,
+    men_preferences: &mut HashMap<u32, Vec<u32>>,
     women_preferences: &HashMap<u32, Vec<u32>>,
     engaged_man_woman: &mut HashMap<u32, u32>,
-) {
+    threads: usize,
+    record: bool,
+) -> RoundOutcome {
     // 1. Find all un-engaged men
     // 2. Propose to the highest ranked woman
     // 3. For each woman, reject/engage all proposals
     // 4. update engaged_man_woman
 
-    let unengaged_men = get_unengaged_men(&men_preferences, &engaged_man_woman);
+    let unengaged_men = get_unengaged_men(men_preferences, engaged_man_woman);
 
-    let proposals = create_proposals(&men_preferences, unengaged_men);
-    accept_or_reject_proposals(
-        &mut men_preferences,
-        &women_preferences,
+    let proposals = create_proposals(men_preferences, unengaged_men);
+    return accept_or_reject_proposals(
+        men_preferences,
+        women_preferences,
         engaged_man_woman,
         proposals,
+        threads,
+        record,
     );
 }
 
@@ -154,9 +463,10 @@ fn get_unengaged_men(
 ) -> HashSet<u32> {
     //TODO: Use functional programming style filters here
     let mut unengaged_men: HashSet<u32> = HashSet::new();
-    for (man, _preferences) in men_preferences {
-        let engaged_woman = engaged_man_woman.get(&man);
-        if engaged_woman.is_none() {
+    for (man, preferences) in men_preferences {
+        // A man who has been rejected by everyone on his list stays single
+        let engaged_woman = engaged_man_woman.get(man);
+        if engaged_woman.is_none() && !preferences.is_empty() {
             unengaged_men.insert(*man);
         }
     }
@@ -172,76 +482,180 @@ fn create_proposals(
 
     for man in unengaged_men {
         let preferred_woman = men_preferences.get(&man).unwrap()[0];
-        let proposals_for_this_woman = proposals.get_mut(&preferred_woman);
-        if proposals_for_this_woman.is_none() {
-            proposals.insert(preferred_woman, [man].iter().cloned().collect());
-        } else {
-            proposals_for_this_woman.unwrap().insert(man);
-        }
+        proposals.entry(preferred_woman).or_default().insert(man);
     }
 
     return proposals;
 }
 
+/// Each woman's answer only depends on her own proposals and engagement, so with more than one of
+/// `threads` the answers are worked out concurrently, and then applied in the same order as serially.
+/// The accepted, rejected and broken pairs are only collected if `record` is set
 fn accept_or_reject_proposals(
     men_preferences: &mut HashMap<u32, Vec<u32>>,
     women_preferences: &HashMap<u32, Vec<u32>>,
     engaged_man_woman: &mut HashMap<u32, u32>,
     proposals: HashMap<u32, HashSet<u32>>,
-) {
-    /// Tentatively accepts proposals. The rejections are permanent. The `engaged_man_woman` HashMap represents an unstable engagement. It suddenly
-    /// becomes 'stable' (go read about gale-shapley to understand what stable means) in the final round, when everyone is engaged to someone
-    for (woman, interested_men) in proposals {
-        let best_interested_man =
-            get_best_man_from_men_interested_in_a_woman(woman, women_preferences, &interested_men);
-        let man_currently_engaged_to = get_currently_engaged_man(&engaged_man_woman, &woman);
-
-        if man_currently_engaged_to.is_none() {
-            make_engagement(engaged_man_woman, best_interested_man, woman);
-        } else {
-            let rank_best_interested_man =
-                get_rank(&women_preferences, &woman, &best_interested_man).unwrap();
-            let rank_currently_engaged_man = get_rank(
-                women_preferences,
-                &woman,
-                &man_currently_engaged_to.unwrap(),
-            )
-            .unwrap();
-            if rank_best_interested_man < rank_currently_engaged_man {
-                break_engagement(engaged_man_woman, man_currently_engaged_to.unwrap());
-                make_engagement(engaged_man_woman, best_interested_man, woman);
+    threads: usize,
+    record: bool,
+) -> RoundOutcome {
+    // Tentatively accepts proposals. The rejections are permanent. The `engaged_man_woman` HashMap represents an unstable engagement. It suddenly
+    // becomes 'stable' (go read about gale-shapley to understand what stable means) in the final round, when everyone is engaged to someone
+    let mut accepted: Vec<(u32, u32)> = Vec::new();
+    let mut rejected: Vec<(u32, u32)> = Vec::new();
+    let mut broken: Vec<(u32, u32)> = Vec::new();
+
+    let proposals_by_woman: Vec<(u32, &HashSet<u32>)> = proposals
+        .iter()
+        .map(|(woman, interested_men)| (*woman, interested_men))
+        .collect();
+    let decisions = decide_all(
+        &proposals_by_woman,
+        women_preferences,
+        engaged_man_woman,
+        threads,
+    );
+
+    for (&(woman, interested_men), (accepted_man, left_man)) in
+        proposals_by_woman.iter().zip(decisions)
+    {
+        if let Some(left_man) = left_man {
+            break_engagement(engaged_man_woman, left_man);
+            if record {
+                broken.push((left_man, woman));
             }
         }
+        if let Some(accepted_man) = accepted_man {
+            make_engagement(engaged_man_woman, accepted_man, woman);
+        }
 
-        for man in &interested_men {
+        for man in interested_men {
             men_preferences.get_mut(man).unwrap().remove(0);
+            if !record {
+                continue;
+            }
+            if accepted_man == Some(*man) {
+                accepted.push((*man, woman));
+            } else {
+                rejected.push((*man, woman));
+            }
         }
     }
+
+    return RoundOutcome {
+        proposals,
+        accepted,
+        rejected,
+        broken,
+    };
+}
+
+/// Smallest number of women who got proposals that `decide_all` hands to a thread of its own
+///
+/// Starting and joining a thread takes about 20µs, and a woman's decision about 0.4µs with lists of 50
+/// men, more with longer lists. Below 64 women, a thread would spend more time starting than deciding
+#[cfg(feature = "parallel")]
+const MIN_WOMEN_PER_THREAD: usize = 64;
+
+/// `decide` for every woman who got proposals, in order, spread over `threads` threads
+#[cfg(feature = "parallel")]
+fn decide_all(
+    proposals_by_woman: &[(u32, &HashSet<u32>)],
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    engaged_man_woman: &HashMap<u32, u32>,
+    threads: usize,
+) -> Vec<(Option<u32>, Option<u32>)> {
+    // Rounds where few women got proposals are not worth starting threads for
+    let threads = threads.min(proposals_by_woman.len() / MIN_WOMEN_PER_THREAD);
+    if threads <= 1 {
+        return decide_serially(proposals_by_woman, women_preferences, engaged_man_woman);
+    }
+
+    let chunk_size = proposals_by_woman.len().div_ceil(threads);
+    return thread::scope(|scope| {
+        let handles: Vec<_> = proposals_by_woman
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || decide_serially(chunk, women_preferences, engaged_man_woman))
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+}
+
+/// `decide` for every woman who got proposals, in order. Without the `parallel` feature there is only one thread
+#[cfg(not(feature = "parallel"))]
+fn decide_all(
+    proposals_by_woman: &[(u32, &HashSet<u32>)],
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    engaged_man_woman: &HashMap<u32, u32>,
+    _threads: usize,
+) -> Vec<(Option<u32>, Option<u32>)> {
+    return decide_serially(proposals_by_woman, women_preferences, engaged_man_woman);
+}
+
+fn decide_serially(
+    proposals_by_woman: &[(u32, &HashSet<u32>)],
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    engaged_man_woman: &HashMap<u32, u32>,
+) -> Vec<(Option<u32>, Option<u32>)> {
+    return proposals_by_woman
+        .iter()
+        .map(|(woman, interested_men)| {
+            decide(*woman, interested_men, women_preferences, engaged_man_woman)
+        })
+        .collect();
+}
+
+/// The man a woman accepts among `interested_men`, if any, and the man she leaves for him, if any
+fn decide(
+    woman: u32,
+    interested_men: &HashSet<u32>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    engaged_man_woman: &HashMap<u32, u32>,
+) -> (Option<u32>, Option<u32>) {
+    let best_interested_man =
+        get_best_man_from_men_interested_in_a_woman(woman, women_preferences, interested_men);
+    let man_currently_engaged_to = get_currently_engaged_man(engaged_man_woman, &woman);
+
+    return match (best_interested_man, man_currently_engaged_to) {
+        // None of the men are on her list
+        (None, _) => (None, None),
+        (Some(best_interested_man), None) => (Some(best_interested_man), None),
+        (Some(best_interested_man), Some(man_currently_engaged_to)) => {
+            let rank_best_interested_man =
+                get_rank(women_preferences, &woman, &best_interested_man).unwrap();
+            let rank_currently_engaged_man =
+                get_rank(women_preferences, &woman, &man_currently_engaged_to).unwrap();
+            if rank_best_interested_man < rank_currently_engaged_man {
+                (Some(best_interested_man), Some(man_currently_engaged_to))
+            } else {
+                (None, None)
+            }
+        }
+    };
 }
 
 fn get_best_man_from_men_interested_in_a_woman(
     woman: u32,
     women_preferences: &HashMap<u32, Vec<u32>>,
     interested_men: &HashSet<u32>,
-) -> u32 {
+) -> Option<u32> {
+    // Returns None if none of the interested men are acceptable to the woman
     //TODO: We do not need the entire women_preferences here. Just the preferences of 'woman' would suffice
     let men_rankings = women_preferences.get(&woman).unwrap();
-    let mut best_man = 0; // TODO: Get rid of this ugly initialization
-    let mut found = false;
 
     for man in men_rankings {
         if interested_men.contains(man) {
-            best_man = *man;
-            found = true;
-            break;
+            return Some(*man);
         }
     }
 
-    if !found {
-        panic!("Could not find best man from a set of interested men!");
-    }
-
-    return best_man;
+    return None;
 }
 
 fn get_rank(preferences: &HashMap<u32, Vec<u32>>, preferences_of: &u32, item: &u32) -> Option<u32> {
@@ -261,7 +675,7 @@ fn get_rank(preferences: &HashMap<u32, Vec<u32>>, preferences_of: &u32, item: &u
 }
 
 fn get_currently_engaged_man(engaged_man_woman: &HashMap<u32, u32>, woman: &u32) -> Option<u32> {
-    /// Returns the man a woman is currently engaged to
+    // Returns the man a woman is currently engaged to
     for (man, engaged_woman) in engaged_man_woman {
         if *engaged_woman == *woman {
             return Some(*man);
@@ -280,12 +694,11 @@ fn break_engagement(engaged_man_woman: &mut HashMap<u32, u32>, man: u32) {
 }
 
 #[cfg(test)]
-
 mod tests {
     use super::*;
     // const sample_size = 5;
 
-    fn get_preferences_config_1() -> HashMap<u32, Vec<u32>> {
+    pub(crate) fn get_preferences_config_1() -> HashMap<u32, Vec<u32>> {
         let mut preferences = HashMap::new();
         preferences.insert(0, vec![0, 1, 2, 3, 4]);
         preferences.insert(1, vec![4, 3, 2, 1, 0]);
@@ -296,7 +709,7 @@ mod tests {
         return preferences;
     }
 
-    fn get_preferences_config_2() -> HashMap<u32, Vec<u32>> {
+    pub(crate) fn get_preferences_config_2() -> HashMap<u32, Vec<u32>> {
         let mut preferences = HashMap::new();
         preferences.insert(0, vec![0, 1, 2, 3, 4]);
         preferences.insert(1, vec![1, 2, 4, 3, 0]);
@@ -346,7 +759,7 @@ mod tests {
         women_preferences.insert(3, vec![0, 1, 2, 3, 4]);
         women_preferences.insert(4, vec![0, 1, 2, 3, 4]);
 
-        let proposals = create_proposals(&mut men_preferences, unengaged_men);
+        let proposals = create_proposals(&men_preferences, unengaged_men);
 
         assert_eq!(
             proposals.get(&0),
@@ -358,8 +771,8 @@ mod tests {
     fn test_create_proposals_base_case_with_better_preferences() {
         // Testing the base case, i.e create proposals in the first round when no man is engaged to a woman
         let unengaged_men: HashSet<u32> = [0, 1, 2, 3, 4].iter().cloned().collect();
-        let mut men_preferences = get_preferences_config_1();
-        let proposals = create_proposals(&mut men_preferences, unengaged_men);
+        let men_preferences = get_preferences_config_1();
+        let proposals = create_proposals(&men_preferences, unengaged_men);
 
         assert_eq!(proposals.get(&0), Some(&vec![0, 2].into_iter().collect()));
         assert_eq!(proposals.get(&2), Some(&vec![3].into_iter().collect()));
@@ -374,14 +787,39 @@ mod tests {
 
         let best_man =
             get_best_man_from_men_interested_in_a_woman(woman, &women_preferences, &interested_men);
-        assert_eq!(best_man, 1);
+        assert_eq!(best_man, Some(1));
 
         let woman: u32 = 2;
         let interested_men: HashSet<u32> = vec![0, 1, 2, 3, 4].into_iter().collect();
 
         let best_man =
             get_best_man_from_men_interested_in_a_woman(woman, &women_preferences, &interested_men);
-        assert_eq!(best_man, 2);
+        assert_eq!(best_man, Some(2));
+    }
+
+    #[test]
+    fn test_get_best_man_from_unacceptable_men() {
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(0, vec![1, 2]);
+
+        let interested_men: HashSet<u32> = vec![0, 3].into_iter().collect();
+        let best_man =
+            get_best_man_from_men_interested_in_a_woman(0, &women_preferences, &interested_men);
+        assert_eq!(best_man, None);
+    }
+
+    #[test]
+    fn test_get_unengaged_men_skips_men_without_choices() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(0, vec![1]);
+        men_preferences.insert(1, vec![]);
+        men_preferences.insert(2, vec![0]);
+
+        let mut engaged_man_woman = HashMap::new();
+        engaged_man_woman.insert(2, 0);
+
+        let unengaged_men = get_unengaged_men(&men_preferences, &engaged_man_woman);
+        assert_eq!(unengaged_men, vec![0].into_iter().collect());
     }
 
     #[test]
@@ -442,12 +880,17 @@ mod tests {
         // 0 - {0, 2}
         // 2 - {3}
         // 4 - {1, 4}
-        accept_or_reject_proposals(
+        let outcome = accept_or_reject_proposals(
             &mut men_preferences,
             &women_preferences,
             &mut engaged_man_woman,
             proposals,
+            1,
+            false,
         );
+        // nobody is watching, so only the proposals are kept
+        assert_eq!(outcome.proposals.len(), 3);
+        assert!(outcome.accepted.is_empty() && outcome.rejected.is_empty());
         assert_eq!(engaged_man_woman.get(&0), Some(&0));
         assert_eq!(engaged_man_woman.get(&1), None);
         assert_eq!(engaged_man_woman.get(&2), None);
@@ -457,7 +900,7 @@ mod tests {
 
         // Another round. Men 1 & 2
         let unengaged_men = get_unengaged_men(&men_preferences, &engaged_man_woman);
-        let proposals = create_proposals(&mut men_preferences, unengaged_men);
+        let proposals = create_proposals(&men_preferences, unengaged_men);
         println!("proposals: {:?}", proposals);
         // proposals would be
         // 1 - {2}
@@ -467,6 +910,8 @@ mod tests {
             &women_preferences,
             &mut engaged_man_woman,
             proposals,
+            1,
+            false,
         );
         println!("engaged: {:?}", engaged_man_woman);
         assert_eq!(engaged_man_woman.get(&0), Some(&0));
@@ -480,4 +925,42 @@ mod tests {
             0
         );
     }
+
+    #[test]
+    fn test_parallel_rounds_match_serial_rounds() {
+        // Large enough for the first rounds to have proposals for more than 2 * MIN_WOMEN_PER_THREAD women
+        let instance = generator::incomplete(260, 240, 0.7, 3);
+
+        let play = |threads: usize| {
+            let mut rounds = Vec::new();
+            let engaged_man_woman = continue_gale_shapley_internal(
+                instance.men.clone(),
+                &instance.women,
+                HashMap::new(),
+                threads,
+                Some(&mut |outcome: &RoundOutcome| {
+                    // Each run iterates its HashMaps in its own order, so only the contents are compared
+                    let sorted = |pairs: &Vec<(u32, u32)>| {
+                        let mut pairs = pairs.clone();
+                        pairs.sort();
+                        pairs
+                    };
+                    rounds.push((
+                        outcome.proposals.clone(),
+                        sorted(&outcome.accepted),
+                        sorted(&outcome.rejected),
+                        sorted(&outcome.broken),
+                    ))
+                }),
+            );
+            (engaged_man_woman, rounds)
+        };
+
+        let serial = play(1);
+        assert!(serial.1.len() > 1);
+        #[cfg(feature = "parallel")]
+        assert!(serial.1[0].0.len() >= 2 * MIN_WOMEN_PER_THREAD);
+        assert_eq!(play(4), serial);
+        assert_eq!(play(100), serial);
+    }
 }
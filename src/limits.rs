@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/**
+    Tells a running `Solver::try_solve` to stop
+
+    Clones share the same flag, so one clone can be handed to the solve and another kept to cancel it,
+    from any thread. The solve notices at the start of its next round.
+
+    # Examples
+    ```
+    use matchertools::CancellationToken;
+
+    let token = CancellationToken::new();
+    let handle = token.clone();
+    handle.cancel();
+    assert!(token.is_cancelled());
+    ```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// A token that is not cancelled yet
+    pub fn new() -> CancellationToken {
+        return CancellationToken::default();
+    }
+
+    /// Cancels every solve that was given this token or one of its clones
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true once `cancel` has been called on this token or one of its clones
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
+
+/// When `Solver::try_solve` should give up. Without any limit set, it runs to the end like `solve`
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    max_rounds: Option<usize>,
+    max_proposals: Option<usize>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Limits {
+    /// No limits
+    pub fn new() -> Limits {
+        return Limits::default();
+    }
+
+    /// Stops after `rounds` rounds if some man is still free to propose
+    pub fn max_rounds(mut self, rounds: usize) -> Limits {
+        self.max_rounds = Some(rounds);
+        return self;
+    }
+
+    /// Stops before the round that would take the total number of proposals over `proposals`
+    pub fn max_proposals(mut self, proposals: usize) -> Limits {
+        self.max_proposals = Some(proposals);
+        return self;
+    }
+
+    /// Stops once `token` is cancelled
+    pub fn cancellation(mut self, token: CancellationToken) -> Limits {
+        self.cancellation = Some(token);
+        return self;
+    }
+
+    /// Why the next round cannot be played, if it cannot. `proposals` counts the ones it would make
+    pub(crate) fn check(&self, rounds_played: usize, proposals: usize) -> Option<Interruption> {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+        {
+            return Some(Interruption::Cancelled);
+        }
+        if self.max_rounds.is_some_and(|max| rounds_played >= max) {
+            return Some(Interruption::RoundBudget);
+        }
+        if self.max_proposals.is_some_and(|max| proposals > max) {
+            return Some(Interruption::ProposalBudget);
+        }
+
+        return None;
+    }
+
+    /// How many more proposals can be made after `proposals`
+    pub(crate) fn proposals_left(&self, proposals: usize) -> usize {
+        return self
+            .max_proposals
+            .map_or(usize::MAX, |max| max.saturating_sub(proposals));
+    }
+}
+
+/// Which of the `Limits` stopped a solve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interruption {
+    /// The `CancellationToken` was cancelled
+    Cancelled,
+    /// `max_rounds` rounds were played
+    RoundBudget,
+    /// One more round, or with `Algorithm::Sequential` one more proposal, would go over `max_proposals`
+    ProposalBudget,
+}
+
+impl fmt::Display for Interruption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Interruption::Cancelled => write!(f, "cancelled"),
+            Interruption::RoundBudget => write!(f, "round budget reached"),
+            Interruption::ProposalBudget => write!(f, "proposal budget reached"),
+        };
+    }
+}
+
+/// The error `Solver::try_solve` returns when it stops early, with what it had reached so far
+#[derive(Debug, Clone)]
+pub struct Interrupted<'a, T> {
+    /// Which limit was reached
+    pub reason: Interruption,
+    /// The tentative engagements after the last round played. They are not stable yet
+    pub engagements: HashMap<&'a T, &'a T>,
+    /// Number of rounds played
+    pub rounds: usize,
+    /// Number of proposals made
+    pub proposals: usize,
+}
+
+impl<'a, T> PartialEq for Interrupted<'a, T>
+where
+    T: Eq + Hash,
+{
+    fn eq(&self, other: &Self) -> bool {
+        return self.reason == other.reason
+            && self.engagements == other.engagements
+            && self.rounds == other.rounds
+            && self.proposals == other.proposals;
+    }
+}
+
+impl<'a, T> fmt::Display for Interrupted<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(
+            f,
+            "stopped after {} rounds and {} proposals: {}",
+            self.rounds, self.proposals, self.reason
+        );
+    }
+}
+
+impl<'a, T> Error for Interrupted<'a, T> where T: fmt::Debug {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator, Algorithm, Solver};
+
+    #[test]
+    fn test_budgets_are_never_exceeded() {
+        let instance = generator::uniform(30, 30, 5);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+            let solver = Solver::new().algorithm(*algorithm);
+            let (_, statistics) =
+                solver.solve_with_statistics(&men_preferences, &women_preferences);
+
+            let limits = Limits::new().max_proposals(statistics.proposals - 1);
+            let interrupted = solver
+                .try_solve(&men_preferences, &women_preferences, &limits)
+                .unwrap_err();
+            assert_eq!(interrupted.reason, Interruption::ProposalBudget);
+            assert!(interrupted.proposals < statistics.proposals);
+            assert!(!interrupted.engagements.is_empty());
+
+            let limits = Limits::new().max_rounds(3);
+            let interrupted = solver
+                .try_solve(&men_preferences, &women_preferences, &limits)
+                .unwrap_err();
+            assert_eq!(interrupted.reason, Interruption::RoundBudget);
+            assert_eq!(interrupted.rounds, 3);
+            assert_eq!(
+                interrupted.to_string(),
+                format!(
+                    "stopped after 3 rounds and {} proposals: round budget reached",
+                    interrupted.proposals
+                )
+            );
+
+            let limits = Limits::new()
+                .max_rounds(statistics.rounds)
+                .max_proposals(statistics.proposals);
+            assert_eq!(
+                solver.try_solve(&men_preferences, &women_preferences, &limits),
+                Ok(crate::gale_shapley(&men_preferences, &women_preferences))
+            );
+        }
+    }
+
+    #[test]
+    fn test_sequential_stops_right_before_the_budget() {
+        let instance = generator::uniform(8, 8, 2);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let solver = Solver::new().algorithm(Algorithm::Sequential);
+
+        let (_, statistics) = solver.solve_with_statistics(&men_preferences, &women_preferences);
+        for budget in 0..statistics.proposals {
+            let limits = Limits::new().max_proposals(budget);
+            let interrupted = solver
+                .try_solve(&men_preferences, &women_preferences, &limits)
+                .unwrap_err();
+            assert_eq!(interrupted.reason, Interruption::ProposalBudget);
+            assert_eq!(interrupted.proposals, budget);
+
+            // every woman is engaged to a different man
+            let mut women: Vec<&u32> = interrupted.engagements.values().cloned().collect();
+            women.sort_unstable();
+            women.dedup();
+            assert_eq!(women.len(), interrupted.engagements.len());
+        }
+    }
+
+    #[test]
+    fn test_cancelled_solve_stops_before_proposing() {
+        let instance = generator::uniform(5, 5, 1);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        let token = CancellationToken::new();
+        let limits = Limits::new().cancellation(token.clone());
+        token.cancel();
+
+        for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+            let interrupted = Solver::new()
+                .algorithm(*algorithm)
+                .try_solve(&men_preferences, &women_preferences, &limits)
+                .unwrap_err();
+            assert_eq!(interrupted.reason, Interruption::Cancelled);
+            assert_eq!(interrupted.rounds, 0);
+            assert!(interrupted.engagements.is_empty());
+        }
+    }
+}
@@ -0,0 +1,375 @@
+//! How much a woman can gain by lying about her preferences
+//!
+//! `gale_shapley` is strategy-proof for the men, but not for the women. A woman can end up with a
+//! better partner by reporting a list that is not her true one. Ranks are indexed from zero and
+//! always refer to her true list.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{gale_shapley_internal, Interned};
+
+/// The kinds of lies a woman can tell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Report her true list, cut short. Everyone after the cut is unacceptable. The best truncation
+    /// gets her her woman-optimal stable partner
+    Truncation,
+    /// Report a complete list in any order, following the optimal cheating strategy of Teo, Sethuraman
+    /// and Tan (2001)
+    OptimalCheating,
+}
+
+/// The best lie a woman can tell. See `best_manipulation`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manipulation<'a, T> {
+    /// The woman who lies
+    pub woman: &'a T,
+    /// The list she should report
+    pub reported_preferences: Vec<&'a T>,
+    /// Her partner if she tells the truth
+    pub truthful_partner: Option<&'a T>,
+    /// Her partner if she reports `reported_preferences`
+    pub manipulated_partner: Option<&'a T>,
+    /// Rank of `truthful_partner` on her true list
+    pub truthful_rank: Option<usize>,
+    /// Rank of `manipulated_partner` on her true list
+    pub manipulated_rank: Option<usize>,
+}
+
+impl<'a, T> Manipulation<'a, T> {
+    /// Returns true if lying gets her a better partner
+    pub fn is_profitable(&self) -> bool {
+        return match (self.truthful_rank, self.manipulated_rank) {
+            (Some(truthful_rank), Some(manipulated_rank)) => manipulated_rank < truthful_rank,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+    }
+
+    /// How many places up her true list her partner moves by lying. 0 if lying does not help
+    pub fn rank_gain(&self) -> usize {
+        return match (self.truthful_rank, self.manipulated_rank) {
+            (Some(truthful_rank), Some(manipulated_rank)) => {
+                truthful_rank.saturating_sub(manipulated_rank)
+            }
+            _ => 0,
+        };
+    }
+}
+
+/**
+    Finds the list `woman` should report to get the best partner she can, assuming everyone else tells the truth
+
+    Returns `None` if `woman` is not one of the keys of `input_women_preferences`. If no lie helps her,
+    the returned `reported_preferences` is her true list.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::manipulation::{self, Strategy};
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let manipulation = manipulation::best_manipulation(
+        &men_preferences,
+        &women_preferences,
+        &"cleopatra",
+        Strategy::Truncation,
+    )
+    .unwrap();
+
+    assert_eq!(manipulation.truthful_partner, Some(&"julius"));
+    assert_eq!(manipulation.manipulated_partner, Some(&"antony"));
+    assert_eq!(manipulation.reported_preferences, vec![&"antony"]);
+    assert_eq!(manipulation.rank_gain(), 1);
+    ```
+*/
+pub fn best_manipulation<'a, T>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    woman: &T,
+    strategy: Strategy,
+) -> Option<Manipulation<'a, T>>
+where
+    T: Eq + Hash,
+{
+    let interned = Interned::new(input_men_preferences, input_women_preferences);
+    let woman_u32 = *interned.women_reference_to_u32.get(woman)?;
+    let true_preferences = interned.women_preferences.get(&woman_u32).unwrap();
+
+    let truthful_partner = partner_of(
+        &interned.men_preferences,
+        &interned.women_preferences,
+        woman_u32,
+    );
+
+    let reported_preferences = match strategy {
+        Strategy::Truncation => best_truncation(&interned, woman_u32),
+        Strategy::OptimalCheating => optimal_cheating(&interned, woman_u32, truthful_partner),
+    };
+    let reported_preferences = reported_preferences.unwrap_or_else(|| true_preferences.clone());
+
+    let mut women_preferences = interned.women_preferences.clone();
+    women_preferences.insert(woman_u32, reported_preferences.clone());
+    let manipulated_partner = partner_of(&interned.men_preferences, &women_preferences, woman_u32);
+
+    let rank =
+        |man: Option<u32>| man.and_then(|man| true_preferences.iter().position(|m| *m == man));
+
+    return Some(Manipulation {
+        woman: interned.woman(woman_u32),
+        reported_preferences: reported_preferences
+            .iter()
+            .map(|man| interned.man(*man))
+            .collect(),
+        truthful_partner: truthful_partner.map(|man| interned.man(man)),
+        manipulated_partner: manipulated_partner.map(|man| interned.man(man)),
+        truthful_rank: rank(truthful_partner),
+        manipulated_rank: rank(manipulated_partner),
+    });
+}
+
+/// The partner of `woman` in the man-optimal matching
+fn partner_of(
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    woman: u32,
+) -> Option<u32> {
+    let engaged_man_woman = gale_shapley_internal(men_preferences, women_preferences, None);
+    for (man, engaged_woman) in engaged_man_woman {
+        if engaged_woman == woman {
+            return Some(man);
+        }
+    }
+
+    return None;
+}
+
+/// The shortest prefix of her true list that still gets her a partner. That partner is the best she can get by truncating
+fn best_truncation<T>(interned: &Interned<T>, woman: u32) -> Option<Vec<u32>> {
+    let true_preferences = interned.women_preferences.get(&woman).unwrap();
+    let mut women_preferences = interned.women_preferences.clone();
+
+    for length in 1..=true_preferences.len() {
+        let truncated = true_preferences[..length].to_vec();
+        women_preferences.insert(woman, truncated.clone());
+        if partner_of(&interned.men_preferences, &women_preferences, woman).is_some() {
+            return Some(truncated);
+        }
+    }
+
+    return None;
+}
+
+/// Teo, Sethuraman and Tan: she can get man `m` with some list only if `m` ends up proposing to her
+/// when she accepts nobody but him. For each such man, best first, put him at the top of her list
+/// and keep demoting whoever she ends up with instead to the bottom, until she gets him
+fn optimal_cheating<T>(
+    interned: &Interned<T>,
+    woman: u32,
+    truthful_partner: Option<u32>,
+) -> Option<Vec<u32>> {
+    let true_preferences = interned.women_preferences.get(&woman).unwrap();
+    let mut women_preferences = interned.women_preferences.clone();
+
+    for man in true_preferences
+        .iter()
+        .take_while(|man| Some(**man) != truthful_partner)
+    {
+        women_preferences.insert(woman, vec![*man]);
+        if partner_of(&interned.men_preferences, &women_preferences, woman) != Some(*man) {
+            continue;
+        }
+
+        let mut reported_preferences = vec![*man];
+        reported_preferences.extend(true_preferences.iter().filter(|m| *m != man));
+        for _ in 0..true_preferences.len() {
+            women_preferences.insert(woman, reported_preferences.clone());
+            match partner_of(&interned.men_preferences, &women_preferences, woman) {
+                Some(partner) if partner == *man => return Some(reported_preferences),
+                Some(partner) => {
+                    reported_preferences.retain(|m| *m != partner);
+                    reported_preferences.push(partner);
+                }
+                None => break,
+            }
+        }
+    }
+
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator;
+
+    type Preferences = HashMap<&'static u32, Vec<&'static u32>>;
+
+    fn get_preferences() -> (Preferences, Preferences) {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1, &2]);
+        men_preferences.insert(&1, vec![&1, &2, &0]);
+        men_preferences.insert(&2, vec![&2, &0, &1]);
+
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&1, &2, &0]);
+        women_preferences.insert(&1, vec![&2, &0, &1]);
+        women_preferences.insert(&2, vec![&0, &1, &2]);
+
+        return (men_preferences, women_preferences);
+    }
+
+    #[test]
+    fn test_truncation_gets_woman_optimal_partner() {
+        let (men_preferences, women_preferences) = get_preferences();
+
+        // Everyone gets their last choice under gale_shapley. Her woman-optimal partner is her first choice
+        let manipulation = best_manipulation(
+            &men_preferences,
+            &women_preferences,
+            &0,
+            Strategy::Truncation,
+        )
+        .unwrap();
+
+        assert_eq!(manipulation.truthful_rank, Some(2));
+        assert_eq!(manipulation.manipulated_rank, Some(0));
+        assert_eq!(manipulation.reported_preferences, vec![&1]);
+        assert!(manipulation.is_profitable());
+        assert_eq!(manipulation.rank_gain(), 2);
+    }
+
+    #[test]
+    fn test_optimal_cheating_needs_a_second_proposal() {
+        let (men_preferences, women_preferences) = get_preferences();
+
+        // Nobody but her partner ever proposes to her, so no complete list can help her
+        let manipulation = best_manipulation(
+            &men_preferences,
+            &women_preferences,
+            &0,
+            Strategy::OptimalCheating,
+        )
+        .unwrap();
+
+        assert!(!manipulation.is_profitable());
+        assert_eq!(manipulation.reported_preferences, vec![&1, &2, &0]);
+    }
+
+    #[test]
+    fn test_optimal_cheating_demotes_blocking_man() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&1, &0, &2, &3]);
+        men_preferences.insert(&1, vec![&1, &3, &0, &2]);
+        men_preferences.insert(&2, vec![&0, &1, &2, &3]);
+        men_preferences.insert(&3, vec![&0, &3, &2, &1]);
+
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&1, &3, &2, &0]);
+        women_preferences.insert(&1, vec![&0, &1, &2, &3]);
+        women_preferences.insert(&2, vec![&3, &2, &0, &1]);
+        women_preferences.insert(&3, vec![&2, &0, &3, &1]);
+
+        // Moving man 1 to the top is not enough: holding on to man 3 stops the rejection that sends man 1 to her
+        let manipulation = best_manipulation(
+            &men_preferences,
+            &women_preferences,
+            &0,
+            Strategy::OptimalCheating,
+        )
+        .unwrap();
+
+        assert_eq!(manipulation.truthful_partner, Some(&3));
+        assert_eq!(manipulation.manipulated_partner, Some(&1));
+        assert_eq!(manipulation.reported_preferences, vec![&1, &2, &0, &3]);
+        assert_eq!(manipulation.rank_gain(), 1);
+    }
+
+    #[test]
+    fn test_no_profitable_manipulation() {
+        let (men_preferences, mut women_preferences) = get_preferences();
+        // Now woman 0 gets her first choice by telling the truth
+        women_preferences.insert(&0, vec![&0, &1, &2]);
+
+        let manipulation = best_manipulation(
+            &men_preferences,
+            &women_preferences,
+            &0,
+            Strategy::Truncation,
+        )
+        .unwrap();
+        assert!(!manipulation.is_profitable());
+        assert_eq!(manipulation.rank_gain(), 0);
+
+        assert!(best_manipulation(
+            &men_preferences,
+            &women_preferences,
+            &7,
+            Strategy::Truncation
+        )
+        .is_none());
+    }
+
+    /// Every order of `men`
+    fn permutations(men: &[u32]) -> Vec<Vec<u32>> {
+        if men.is_empty() {
+            return vec![Vec::new()];
+        }
+
+        let mut orders = Vec::new();
+        for (index, man) in men.iter().enumerate() {
+            let mut rest = men.to_vec();
+            rest.remove(index);
+            for mut order in permutations(&rest) {
+                order.insert(0, *man);
+                orders.push(order);
+            }
+        }
+        return orders;
+    }
+
+    #[test]
+    fn test_optimal_cheating_matches_every_complete_list() {
+        for seed in 0..40 {
+            let n = 2 + seed as usize % 4;
+            let instance = generator::uniform(n, n, seed);
+            let men_preferences = instance.men_preferences();
+            let women_preferences = instance.women_preferences();
+            let interned = Interned::new(&men_preferences, &women_preferences);
+
+            for woman in instance.women.keys() {
+                let manipulation = best_manipulation(
+                    &men_preferences,
+                    &women_preferences,
+                    woman,
+                    Strategy::OptimalCheating,
+                )
+                .unwrap();
+
+                let woman = *interned.women_reference_to_u32.get(woman).unwrap();
+                let true_preferences = interned.women_preferences.get(&woman).unwrap();
+                let mut reported_preferences = interned.women_preferences.clone();
+                let best_rank = permutations(true_preferences)
+                    .into_iter()
+                    .filter_map(|permutation| {
+                        reported_preferences.insert(woman, permutation);
+                        let partner =
+                            partner_of(&interned.men_preferences, &reported_preferences, woman)?;
+                        return true_preferences.iter().position(|man| *man == partner);
+                    })
+                    .min();
+
+                assert_eq!(manipulation.manipulated_rank, best_rank, "seed {}", seed);
+            }
+        }
+    }
+}
@@ -0,0 +1,503 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::warm_start::would_accept;
+
+/**
+    A market that people keep joining and leaving, with a stable matching that is kept up to date
+
+    Every change re-stabilizes the matching starting from the current one, so only the people the
+    change actually affects get a new partner. The work done is proportional to the proposals the
+    change sets off rather than to the size of the market.
+
+    Like `gale_shapley`, the men propose. A market built in one go with `from_preferences` holds the
+    man-optimal matching. After later changes the matching is still stable, but it is the one
+    closest to the previous matching rather than the man-optimal one.
+
+    Preference lists may mention people who are not in the market (yet). They are skipped until they join.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::Market;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let mut market = Market::from_preferences(&men_preferences, &women_preferences);
+    assert_eq!(market.partner_of_man(&"antony"), Some(&"cleopatra"));
+
+    let update = market.remove_man(&"antony");
+    assert_eq!(market.partner_of_man(&"julius"), Some(&"cleopatra"));
+    assert_eq!(update.men.len(), 2);
+    assert_eq!(update.women.len(), 2);
+
+    let change = update.women.get(&"boudica").unwrap();
+    assert_eq!(change.before, Some("julius"));
+    assert_eq!(change.after, None);
+    ```
+*/
+#[derive(Debug, Clone)]
+pub struct Market<T> {
+    men: Names<T>,
+    women: Names<T>,
+    men_preferences: HashMap<u32, Vec<u32>>,
+    women_preferences: HashMap<u32, Vec<u32>>,
+    /// woman -> the men in the market who have her on their list
+    listed_by: HashMap<u32, HashSet<u32>>,
+    /// index into each man's preferences of the next woman he will propose to
+    next_choice: HashMap<u32, usize>,
+    engaged_man_woman: HashMap<u32, u32>,
+    engaged_woman_man: HashMap<u32, u32>,
+}
+
+/// How someone's partner changed. `None` means single
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartnerChange<T> {
+    /// Partner before the change
+    pub before: Option<T>,
+    /// Partner after the change
+    pub after: Option<T>,
+}
+
+/// The people whose partner changed because of a change to a `Market`
+#[derive(Debug, Clone)]
+pub struct MarketUpdate<T> {
+    /// Every man whose partner changed
+    pub men: HashMap<T, PartnerChange<T>>,
+    /// Every woman whose partner changed
+    pub women: HashMap<T, PartnerChange<T>>,
+    /// Number of proposals it took to re-stabilize the market
+    pub proposals: usize,
+}
+
+/// Gives everyone who was ever mentioned in the market a u32, so that the algorithm does not have to care about `T`
+#[derive(Debug, Clone)]
+struct Names<T> {
+    ids: HashMap<T, u32>,
+    names: Vec<T>,
+}
+
+impl<T> Names<T>
+where
+    T: Clone + Eq + Hash,
+{
+    fn new() -> Names<T> {
+        return Names {
+            ids: HashMap::new(),
+            names: Vec::new(),
+        };
+    }
+
+    fn id(&mut self, name: &T) -> u32 {
+        if let Some(id) = self.ids.get(name) {
+            return *id;
+        }
+
+        let id = self.names.len() as u32;
+        self.ids.insert(name.clone(), id);
+        self.names.push(name.clone());
+        return id;
+    }
+
+    fn existing_id(&self, name: &T) -> Option<u32> {
+        return self.ids.get(name).cloned();
+    }
+
+    fn name(&self, id: u32) -> &T {
+        return &self.names[id as usize];
+    }
+}
+
+/// The partners everyone had before the change, recorded the first time their partner changes
+#[derive(Default)]
+struct Changes {
+    men_before: HashMap<u32, Option<u32>>,
+    women_before: HashMap<u32, Option<u32>>,
+    /// Free men who may still have someone to propose to
+    proposers: Vec<u32>,
+    /// Women who may now accept men that they (or their partner) turned down before
+    loosened_women: Vec<u32>,
+    proposals: usize,
+}
+
+impl<T> Default for Market<T>
+where
+    T: Clone + Eq + Hash,
+{
+    fn default() -> Market<T> {
+        return Market::new();
+    }
+}
+
+impl<T> Market<T>
+where
+    T: Clone + Eq + Hash,
+{
+    /// An empty market
+    pub fn new() -> Market<T> {
+        return Market {
+            men: Names::new(),
+            women: Names::new(),
+            men_preferences: HashMap::new(),
+            women_preferences: HashMap::new(),
+            listed_by: HashMap::new(),
+            next_choice: HashMap::new(),
+            engaged_man_woman: HashMap::new(),
+            engaged_woman_man: HashMap::new(),
+        };
+    }
+
+    /// A market holding the same input that `gale_shapley` accepts, matched the same way
+    pub fn from_preferences(
+        men_preferences: &HashMap<&T, Vec<&T>>,
+        women_preferences: &HashMap<&T, Vec<&T>>,
+    ) -> Market<T> {
+        let mut market = Market::new();
+        for (woman, preferences) in women_preferences {
+            market.insert_woman(
+                (*woman).clone(),
+                preferences.iter().map(|man| (*man).clone()).collect(),
+            );
+        }
+        for (man, preferences) in men_preferences {
+            market.insert_man(
+                (*man).clone(),
+                preferences.iter().map(|woman| (*woman).clone()).collect(),
+            );
+        }
+
+        return market;
+    }
+
+    /// The current partner of `man`
+    pub fn partner_of_man(&self, man: &T) -> Option<&T> {
+        let man = self.men.existing_id(man)?;
+        return self
+            .engaged_man_woman
+            .get(&man)
+            .map(|woman| self.women.name(*woman));
+    }
+
+    /// The current partner of `woman`
+    pub fn partner_of_woman(&self, woman: &T) -> Option<&T> {
+        let woman = self.women.existing_id(woman)?;
+        return self
+            .engaged_woman_man
+            .get(&woman)
+            .map(|man| self.men.name(*man));
+    }
+
+    /// The current matching. It maps each man to a woman, like the return value of `gale_shapley`
+    pub fn engagements(&self) -> HashMap<&T, &T> {
+        return self
+            .engaged_man_woman
+            .iter()
+            .map(|(man, woman)| (self.men.name(*man), self.women.name(*woman)))
+            .collect();
+    }
+
+    /// Adds `man` to the market. If he is already in it, his preferences are replaced
+    pub fn insert_man(&mut self, man: T, preferences: Vec<T>) -> MarketUpdate<T> {
+        let mut changes = Changes::default();
+        let man = self.men.id(&man);
+
+        self.withdraw_man(man, &mut changes);
+        let preferences: Vec<u32> = preferences
+            .iter()
+            .map(|woman| self.women.id(woman))
+            .collect();
+        for woman in &preferences {
+            self.listed_by.entry(*woman).or_default().insert(man);
+        }
+        self.men_preferences.insert(man, preferences);
+        self.next_choice.insert(man, 0);
+        changes.proposers.push(man);
+
+        return self.restabilize(changes);
+    }
+
+    /// Adds `woman` to the market. If she is already in it, her preferences are replaced
+    pub fn insert_woman(&mut self, woman: T, preferences: Vec<T>) -> MarketUpdate<T> {
+        let mut changes = Changes::default();
+        let woman = self.women.id(&woman);
+
+        let preferences: Vec<u32> = preferences.iter().map(|man| self.men.id(man)).collect();
+        if let Some(man) = self.engaged_woman_man.get(&woman).cloned() {
+            if !preferences.contains(&man) {
+                self.break_engagement(man, &mut changes);
+                changes.proposers.push(man);
+            }
+        }
+        self.women_preferences.insert(woman, preferences);
+        changes.loosened_women.push(woman);
+
+        return self.restabilize(changes);
+    }
+
+    /// Takes `man` out of the market. Does nothing if he is not in it
+    pub fn remove_man(&mut self, man: &T) -> MarketUpdate<T> {
+        let mut changes = Changes::default();
+        if let Some(man) = self.men.existing_id(man) {
+            self.withdraw_man(man, &mut changes);
+        }
+
+        return self.restabilize(changes);
+    }
+
+    /// Takes `woman` out of the market. Does nothing if she is not in it
+    pub fn remove_woman(&mut self, woman: &T) -> MarketUpdate<T> {
+        let mut changes = Changes::default();
+        if let Some(woman) = self.women.existing_id(woman) {
+            if let Some(man) = self.engaged_woman_man.get(&woman).cloned() {
+                self.break_engagement(man, &mut changes);
+                changes.proposers.push(man);
+            }
+            self.women_preferences.remove(&woman);
+        }
+
+        return self.restabilize(changes);
+    }
+
+    fn withdraw_man(&mut self, man: u32, changes: &mut Changes) {
+        if let Some(woman) = self.engaged_man_woman.get(&man).cloned() {
+            self.break_engagement(man, changes);
+            changes.loosened_women.push(woman);
+        }
+        if let Some(preferences) = self.men_preferences.remove(&man) {
+            for woman in preferences {
+                if let Some(men) = self.listed_by.get_mut(&woman) {
+                    men.remove(&man);
+                }
+            }
+        }
+        self.next_choice.remove(&man);
+    }
+
+    fn restabilize(&mut self, mut changes: Changes) -> MarketUpdate<T> {
+        // Every engaged man has been turned down by all the women he prefers to his partner. A
+        // loosened woman may now accept some of those men, who then have to propose to her again
+        while let Some(woman) = changes.loosened_women.pop() {
+            if !self.women_preferences.contains_key(&woman) {
+                continue;
+            }
+
+            let men: Vec<u32> = match self.listed_by.get(&woman) {
+                Some(men) => men.iter().cloned().collect(),
+                None => continue,
+            };
+            for man in men {
+                let position = self
+                    .men_preferences
+                    .get(&man)
+                    .unwrap()
+                    .iter()
+                    .position(|choice| *choice == woman)
+                    .unwrap();
+                let next_choice = *self.next_choice.get(&man).unwrap();
+                if position >= next_choice
+                    || !would_accept(&self.women_preferences, &self.engaged_woman_man, woman, man)
+                {
+                    continue;
+                }
+
+                match self.engaged_man_woman.get(&man).cloned() {
+                    Some(partner) if partner == woman => continue,
+                    Some(partner) => {
+                        self.break_engagement(man, &mut changes);
+                        changes.loosened_women.push(partner);
+                    }
+                    None => {}
+                }
+                self.next_choice.insert(man, position);
+                changes.proposers.push(man);
+            }
+        }
+
+        // Now the men who lost their partner propose, McVitie-Wilson style
+        while let Some(man) = changes.proposers.pop() {
+            self.propose(man, &mut changes);
+        }
+
+        return self.to_update(changes);
+    }
+
+    fn propose(&mut self, man: u32, changes: &mut Changes) {
+        let mut proposer = man;
+
+        while !self.engaged_man_woman.contains_key(&proposer) {
+            let preferences = match self.men_preferences.get(&proposer) {
+                Some(preferences) => preferences,
+                None => return,
+            };
+            let choice = self.next_choice.get_mut(&proposer).unwrap();
+            let woman = match preferences.get(*choice) {
+                Some(woman) => *woman,
+                None => return,
+            };
+            *choice += 1;
+            changes.proposals += 1;
+
+            if !would_accept(
+                &self.women_preferences,
+                &self.engaged_woman_man,
+                woman,
+                proposer,
+            ) {
+                continue;
+            }
+            let current_man = self.engaged_woman_man.get(&woman).cloned();
+            if let Some(current_man) = current_man {
+                self.break_engagement(current_man, changes);
+            }
+            self.make_engagement(proposer, woman, changes);
+            if let Some(current_man) = current_man {
+                proposer = current_man;
+            }
+        }
+    }
+
+    fn record(&self, man: u32, woman: u32, changes: &mut Changes) {
+        let partner_of_man = self.engaged_man_woman.get(&man).cloned();
+        let partner_of_woman = self.engaged_woman_man.get(&woman).cloned();
+        changes.men_before.entry(man).or_insert(partner_of_man);
+        changes
+            .women_before
+            .entry(woman)
+            .or_insert(partner_of_woman);
+    }
+
+    fn make_engagement(&mut self, man: u32, woman: u32, changes: &mut Changes) {
+        self.record(man, woman, changes);
+        self.engaged_man_woman.insert(man, woman);
+        self.engaged_woman_man.insert(woman, man);
+    }
+
+    fn break_engagement(&mut self, man: u32, changes: &mut Changes) {
+        if let Some(woman) = self.engaged_man_woman.get(&man).cloned() {
+            self.record(man, woman, changes);
+            self.engaged_man_woman.remove(&man);
+            self.engaged_woman_man.remove(&woman);
+        }
+    }
+
+    fn to_update(&self, changes: Changes) -> MarketUpdate<T> {
+        let mut men: HashMap<T, PartnerChange<T>> = HashMap::new();
+        for (man, before) in changes.men_before {
+            let after = self.engaged_man_woman.get(&man).cloned();
+            if before != after {
+                men.insert(
+                    self.men.name(man).clone(),
+                    PartnerChange {
+                        before: before.map(|woman| self.women.name(woman).clone()),
+                        after: after.map(|woman| self.women.name(woman).clone()),
+                    },
+                );
+            }
+        }
+
+        let mut women: HashMap<T, PartnerChange<T>> = HashMap::new();
+        for (woman, before) in changes.women_before {
+            let after = self.engaged_woman_man.get(&woman).cloned();
+            if before != after {
+                women.insert(
+                    self.women.name(woman).clone(),
+                    PartnerChange {
+                        before: before.map(|man| self.men.name(man).clone()),
+                        after: after.map(|man| self.men.name(man).clone()),
+                    },
+                );
+            }
+        }
+
+        return MarketUpdate {
+            men,
+            women,
+            proposals: changes.proposals,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_market() -> Market<u32> {
+        let mut market = Market::new();
+        market.insert_woman(0, vec![0, 1, 2]);
+        market.insert_woman(1, vec![1, 0, 2]);
+        market.insert_man(0, vec![0, 1]);
+        market.insert_man(1, vec![0, 1]);
+
+        return market;
+    }
+
+    #[test]
+    fn test_insert_man() {
+        let mut market = get_market();
+        assert_eq!(market.partner_of_man(&0), Some(&0));
+        assert_eq!(market.partner_of_man(&1), Some(&1));
+
+        // Man 2 is last on every list, so nothing happens
+        let update = market.insert_man(2, vec![0, 1]);
+        assert!(update.men.is_empty());
+        assert!(update.women.is_empty());
+        assert_eq!(update.proposals, 2);
+        assert_eq!(market.partner_of_man(&2), None);
+    }
+
+    #[test]
+    fn test_insert_woman_loosens() {
+        let mut market = get_market();
+        market.insert_man(2, vec![0, 1, 2]);
+        assert_eq!(market.partner_of_man(&2), None);
+
+        // Woman 2 was mentioned before she joined
+        let update = market.insert_woman(2, vec![2]);
+        assert_eq!(market.partner_of_man(&2), Some(&2));
+        assert_eq!(update.proposals, 1);
+        assert_eq!(
+            update.men.get(&2),
+            Some(&PartnerChange {
+                before: None,
+                after: Some(2)
+            })
+        );
+    }
+
+    #[test]
+    fn test_edit_woman_preferences() {
+        let mut market = get_market();
+
+        // Woman 1 now prefers man 0, but he is with his first choice, so nothing changes
+        let update = market.insert_woman(1, vec![0, 1]);
+        assert!(update.men.is_empty());
+
+        // Woman 0 drops man 0. He moves on to woman 1, who prefers him to man 1
+        let update = market.insert_woman(0, vec![1, 2]);
+        assert_eq!(market.partner_of_man(&0), Some(&1));
+        assert_eq!(market.partner_of_man(&1), Some(&0));
+        assert_eq!(update.men.len(), 2);
+        assert_eq!(update.women.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_woman() {
+        let mut market = get_market();
+        let update = market.remove_woman(&0);
+
+        // Man 0 is second on the list of woman 1, who keeps man 1
+        assert_eq!(market.partner_of_woman(&0), None);
+        assert_eq!(market.partner_of_man(&0), None);
+        assert_eq!(market.partner_of_man(&1), Some(&1));
+        assert_eq!(update.men.get(&0).unwrap().after, None);
+        assert_eq!(update.proposals, 1);
+        assert_eq!(market.engagements().len(), 1);
+    }
+}
@@ -0,0 +1,310 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::Preferences;
+
+/// How one agent ranks the other side
+#[derive(Debug, Clone)]
+enum Ranking<'a, T> {
+    /// The master list, as it is
+    Master,
+    /// The master list, but only the agents at these (sorted) positions are acceptable
+    Restricted(Vec<usize>),
+    /// A list of their own
+    Own(Vec<&'a T>),
+}
+
+/**
+    Preferences of one side where most agents share the same ranking of the other side
+
+    Everyone follows a single master list, best first, except for the agents who only accept some of
+    it (`restrict`) or who have a list of their own (`set_preferences`). That takes memory in
+    proportion to the master list plus the exceptions, instead of one full list per agent.
+
+    `MasterList` implements `Preferences`, so `Solver::solve_with_preferences` takes it directly.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::{MasterList, Solver};
+
+    let mut men_preferences = HashMap::new();
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    // Both women rank antony first, but boudica will not have him
+    let mut women_preferences =
+        MasterList::new(vec![&"cleopatra", &"boudica"], vec![&"antony", &"julius"]);
+    women_preferences.restrict(&"boudica", vec![&"julius"]);
+
+    let engaged_man_woman = Solver::new().solve_with_preferences(
+        men_preferences.keys().cloned(),
+        &men_preferences,
+        &women_preferences,
+    );
+    assert_eq!(engaged_man_woman.get(&"antony"), Some(&&"cleopatra"));
+    assert_eq!(engaged_man_woman.get(&"julius"), Some(&&"boudica"));
+
+    let lists = women_preferences.to_preferences();
+    assert_eq!(lists.get(&"boudica").unwrap(), &vec![&"julius"]);
+    ```
+*/
+#[derive(Debug, Clone)]
+pub struct MasterList<'a, T> {
+    master: Vec<&'a T>,
+    /// agent -> their position in `master`
+    master_rank: HashMap<&'a T, usize>,
+    rankings: HashMap<&'a T, Ranking<'a, T>>,
+}
+
+impl<'a, T> MasterList<'a, T>
+where
+    T: Eq + Hash,
+{
+    /// Every one of `agents` ranks the other side as `master` does, best first
+    pub fn new<I>(agents: I, master: Vec<&'a T>) -> MasterList<'a, T>
+    where
+        I: IntoIterator<Item = &'a T>,
+    {
+        let master_rank = master
+            .iter()
+            .enumerate()
+            .map(|(rank, agent)| (*agent, rank))
+            .collect();
+
+        return MasterList {
+            master,
+            master_rank,
+            rankings: agents
+                .into_iter()
+                .map(|agent| (agent, Ranking::Master))
+                .collect(),
+        };
+    }
+
+    /**
+        Converts explicit lists, picking a master list that should leave few exceptions
+
+        The master list orders the other side by average position in the lists (the Borda count).
+        That is a heuristic: another master list may leave fewer exceptions. Agents with the same
+        average are ordered by `Ord`, so the same preferences always give the same master list.
+        Whatever master list is picked, `to_preferences` gives back exactly `preferences`.
+    */
+    pub fn from_preferences(preferences: &HashMap<&'a T, Vec<&'a T>>) -> MasterList<'a, T>
+    where
+        T: Ord,
+    {
+        let mut positions: HashMap<&T, Vec<f64>> = HashMap::new();
+        for choices in preferences.values() {
+            for (rank, choice) in choices.iter().enumerate() {
+                positions
+                    .entry(choice)
+                    .or_default()
+                    .push(rank as f64 / choices.len() as f64);
+            }
+        }
+
+        // summed in sorted order, so that the score does not depend on the order of the lists
+        let mut master: Vec<(&T, f64)> = positions
+            .into_iter()
+            .map(|(agent, mut positions)| {
+                positions.sort_by(f64::total_cmp);
+                let average = positions.iter().sum::<f64>() / positions.len() as f64;
+                return (agent, average);
+            })
+            .collect();
+        master.sort_by(|(a, a_score), (b, b_score)| a_score.total_cmp(b_score).then(a.cmp(b)));
+
+        let mut master_list = MasterList::new(
+            preferences.keys().cloned(),
+            master.into_iter().map(|(agent, _)| agent).collect(),
+        );
+        for (agent, choices) in preferences {
+            let ranks: Vec<usize> = choices
+                .iter()
+                .map(|choice| *master_list.master_rank.get(choice).unwrap())
+                .collect();
+
+            let ranking = if ranks.len() == master_list.master.len() && is_increasing(&ranks) {
+                Ranking::Master
+            } else if is_increasing(&ranks) {
+                Ranking::Restricted(ranks)
+            } else {
+                Ranking::Own(choices.clone())
+            };
+            master_list.rankings.insert(agent, ranking);
+        }
+
+        return master_list;
+    }
+
+    /// `agent` keeps the order of the master list, but only accepts `acceptable`. Agents who are not on the master list are ignored
+    pub fn restrict<I>(&mut self, agent: &'a T, acceptable: I)
+    where
+        I: IntoIterator<Item = &'a T>,
+    {
+        let mut ranks: Vec<usize> = acceptable
+            .into_iter()
+            .filter_map(|other| self.master_rank.get(other).cloned())
+            .collect();
+        ranks.sort_unstable();
+        ranks.dedup();
+
+        self.rankings.insert(agent, Ranking::Restricted(ranks));
+    }
+
+    /// `agent` ranks the other side as `preferences` does, best first, instead of following the master list
+    pub fn set_preferences(&mut self, agent: &'a T, preferences: Vec<&'a T>) {
+        self.rankings.insert(agent, Ranking::Own(preferences));
+    }
+
+    /// Number of agents who do not follow the whole master list
+    pub fn exceptions(&self) -> usize {
+        return self
+            .rankings
+            .values()
+            .filter(|ranking| !matches!(ranking, Ranking::Master))
+            .count();
+    }
+
+    /// Every agent's explicit list, in the form the rest of the crate takes
+    pub fn to_preferences(&self) -> HashMap<&'a T, Vec<&'a T>> {
+        return self
+            .rankings
+            .iter()
+            .map(|(agent, ranking)| {
+                let choices = match ranking {
+                    Ranking::Master => self.master.clone(),
+                    Ranking::Restricted(ranks) => {
+                        ranks.iter().map(|rank| self.master[*rank]).collect()
+                    }
+                    Ranking::Own(choices) => choices.clone(),
+                };
+                (*agent, choices)
+            })
+            .collect();
+    }
+
+    /// Position of `other` in `agent`'s preferences, or `None` if `other` is not acceptable
+    fn rank(&self, agent: &'a T, other: &'a T) -> Option<usize> {
+        return match self.rankings.get(agent)? {
+            Ranking::Master => self.master_rank.get(other).cloned(),
+            Ranking::Restricted(ranks) => {
+                let master_rank = self.master_rank.get(other)?;
+                ranks.binary_search(master_rank).ok()
+            }
+            Ranking::Own(choices) => choices.iter().position(|choice| *choice == other),
+        };
+    }
+}
+
+impl<'a, T> Preferences<&'a T> for MasterList<'a, T>
+where
+    T: Eq + Hash,
+{
+    fn next_choice(&self, agent: &'a T, after: Option<&'a T>) -> Option<&'a T> {
+        let position = match after {
+            Some(after) => self.rank(agent, after)? + 1,
+            None => 0,
+        };
+
+        return match self.rankings.get(agent)? {
+            Ranking::Master => self.master.get(position).cloned(),
+            Ranking::Restricted(ranks) => ranks.get(position).map(|rank| self.master[*rank]),
+            Ranking::Own(choices) => choices.get(position).cloned(),
+        };
+    }
+
+    fn prefers(&self, agent: &'a T, a: &'a T, b: &'a T) -> Ordering {
+        let rank_a = self.rank(agent, a).unwrap_or(usize::MAX);
+        let rank_b = self.rank(agent, b).unwrap_or(usize::MAX);
+        return rank_a.cmp(&rank_b);
+    }
+
+    fn is_acceptable(&self, agent: &'a T, other: &'a T) -> bool {
+        return self.rank(agent, other).is_some();
+    }
+}
+
+fn is_increasing(ranks: &[usize]) -> bool {
+    return ranks.windows(2).all(|pair| pair[0] < pair[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Algorithm, Solver};
+
+    #[test]
+    fn test_from_preferences_round_trip() {
+        let mut preferences = HashMap::new();
+        preferences.insert(&0, vec![&0, &1, &2, &3]);
+        preferences.insert(&1, vec![&0, &1, &2, &3]);
+        preferences.insert(&2, vec![&1, &3]);
+        preferences.insert(&3, vec![&0, &2, &1]);
+
+        let master_list = MasterList::from_preferences(&preferences);
+
+        assert_eq!(master_list.master, vec![&0, &1, &2, &3]);
+        assert_eq!(master_list.exceptions(), 2);
+        assert_eq!(master_list.to_preferences(), preferences);
+    }
+
+    #[test]
+    fn test_from_preferences_breaks_ties_by_agent() {
+        // 1 and 2 have the same average position, whatever order the lists are visited in
+        for _ in 0..20 {
+            let mut preferences = HashMap::new();
+            preferences.insert(&0, vec![&2, &1, &3]);
+            preferences.insert(&1, vec![&1, &2, &3]);
+
+            let master_list = MasterList::from_preferences(&preferences);
+            assert_eq!(master_list.master, vec![&1, &2, &3]);
+            assert_eq!(master_list.exceptions(), 1);
+        }
+    }
+
+    #[test]
+    fn test_preferences_follow_rankings() {
+        let mut master_list = MasterList::new(vec![&0, &1, &2], vec![&0, &1, &2, &3]);
+        master_list.restrict(&1, vec![&3, &1, &9]);
+        master_list.set_preferences(&2, vec![&2, &0]);
+
+        assert_eq!(master_list.next_choice(&0, Some(&1)), Some(&2));
+        assert_eq!(master_list.next_choice(&1, None), Some(&1));
+        assert_eq!(master_list.next_choice(&1, Some(&1)), Some(&3));
+        assert_eq!(master_list.next_choice(&1, Some(&3)), None);
+        assert_eq!(master_list.next_choice(&2, Some(&2)), Some(&0));
+        assert_eq!(master_list.next_choice(&7, None), None);
+
+        assert_eq!(master_list.prefers(&1, &3, &0), Ordering::Less);
+        assert_eq!(master_list.prefers(&2, &0, &2), Ordering::Greater);
+        assert!(!master_list.is_acceptable(&1, &0));
+        assert!(master_list.is_acceptable(&0, &3));
+        assert_eq!(master_list.exceptions(), 2);
+    }
+
+    #[test]
+    fn test_excluded_first_choice_moves_on() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1, &2]);
+        men_preferences.insert(&1, vec![&1, &0, &2]);
+        men_preferences.insert(&2, vec![&1, &2, &0]);
+
+        // Woman 0 is man 0's first choice, but she will not have him
+        let mut women_preferences = MasterList::new(vec![&0, &1, &2], vec![&0, &1, &2]);
+        women_preferences.restrict(&0, vec![&1, &2]);
+
+        for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+            let engaged_man_woman = Solver::new().algorithm(*algorithm).solve_with_preferences(
+                vec![&0, &1, &2],
+                &men_preferences,
+                &women_preferences,
+            );
+            assert_eq!(engaged_man_woman.get(&0), Some(&&1));
+            assert_eq!(engaged_man_woman.get(&1), Some(&&0));
+            assert_eq!(engaged_man_woman.get(&2), Some(&&2));
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Measures how good a matching is for each side
+//!
+//! Ranks are indexed from zero, like everywhere else in this crate: an agent matched to their first
+//! choice has rank 0.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Metrics for one side of the market (either the men or the women)
+#[derive(Debug, Clone)]
+pub struct SideMetrics<'a, T> {
+    /// The rank of each agent's partner in that agent's preferences. Unmatched agents, and agents
+    /// whose partner is not on their list, are left out
+    pub ranks: HashMap<&'a T, usize>,
+    /// Sum of `ranks`
+    pub total_rank: usize,
+    /// `total_rank` divided by the number of ranked agents. 0 if nobody is ranked
+    pub average_rank: f64,
+    /// Number of agents matched to their first choice
+    pub first_choices: usize,
+    /// `rank_histogram[r]` is the number of agents whose partner has rank `r`
+    pub rank_histogram: Vec<usize>,
+    /// Number of agents without a partner
+    pub unmatched: usize,
+}
+
+/// Outcome metrics of a matching. See `compute`
+#[derive(Debug, Clone)]
+pub struct Metrics<'a, T> {
+    /// How the men fared
+    pub men: SideMetrics<'a, T>,
+    /// How the women fared
+    pub women: SideMetrics<'a, T>,
+    /// Sum of the ranks of everyone. Lower is better
+    pub egalitarian_cost: usize,
+    /// How unevenly the two sides are treated: the absolute difference of their total ranks
+    pub sex_equality_cost: usize,
+    /// The worst rank anyone got. 0 if nobody is ranked
+    pub regret: usize,
+}
+
+/**
+    Computes the metrics of `engaged_man_woman`, given the preferences it was computed from
+
+    `engaged_man_woman` maps each man to a woman, just like the return value of `gale_shapley`.
+    It does not have to be stable, or even complete.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::metrics;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+    let metrics = metrics::compute(&men_preferences, &women_preferences, &engaged_man_woman);
+
+    assert_eq!(metrics.men.ranks.get(&"julius"), Some(&1));
+    assert_eq!(metrics.men.first_choices, 1);
+    assert_eq!(metrics.women.first_choices, 2);
+    assert_eq!(metrics.egalitarian_cost, 1);
+    assert_eq!(metrics.sex_equality_cost, 1);
+    assert_eq!(metrics.regret, 1);
+    ```
+*/
+pub fn compute<'a, T>(
+    men_preferences: &'a HashMap<&T, Vec<&T>>,
+    women_preferences: &'a HashMap<&T, Vec<&T>>,
+    engaged_man_woman: &HashMap<&T, &T>,
+) -> Metrics<'a, T>
+where
+    T: Eq + Hash,
+{
+    let mut engaged_woman_man: HashMap<&T, &T> = HashMap::new();
+    for (man, woman) in engaged_man_woman {
+        engaged_woman_man.insert(woman, man);
+    }
+
+    let men = compute_side(men_preferences, engaged_man_woman);
+    let women = compute_side(women_preferences, &engaged_woman_man);
+
+    let egalitarian_cost = men.total_rank + women.total_rank;
+    let sex_equality_cost = men.total_rank.abs_diff(women.total_rank);
+    let regret = std::cmp::max(men.rank_histogram.len(), women.rank_histogram.len());
+    let regret = if regret == 0 { 0 } else { regret - 1 };
+
+    return Metrics {
+        men,
+        women,
+        egalitarian_cost,
+        sex_equality_cost,
+        regret,
+    };
+}
+
+fn compute_side<'a, T>(
+    preferences: &'a HashMap<&T, Vec<&T>>,
+    partners: &HashMap<&T, &T>,
+) -> SideMetrics<'a, T>
+where
+    T: Eq + Hash,
+{
+    let mut ranks: HashMap<&T, usize> = HashMap::new();
+    let mut rank_histogram: Vec<usize> = Vec::new();
+    let mut unmatched = 0;
+
+    for (agent, agent_preferences) in preferences {
+        let partner = match partners.get(*agent) {
+            Some(partner) => partner,
+            None => {
+                unmatched += 1;
+                continue;
+            }
+        };
+
+        if let Some(rank) = agent_preferences
+            .iter()
+            .position(|choice| choice == partner)
+        {
+            ranks.insert(agent, rank);
+            if rank_histogram.len() <= rank {
+                rank_histogram.resize(rank + 1, 0);
+            }
+            rank_histogram[rank] += 1;
+        }
+    }
+
+    let total_rank: usize = ranks.values().sum();
+    let average_rank = if ranks.is_empty() {
+        0.0
+    } else {
+        total_rank as f64 / ranks.len() as f64
+    };
+    let first_choices = rank_histogram.first().cloned().unwrap_or(0);
+
+    return SideMetrics {
+        ranks,
+        total_rank,
+        average_rank,
+        first_choices,
+        rank_histogram,
+        unmatched,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_side() {
+        let mut preferences = HashMap::new();
+        preferences.insert(&0, vec![&0, &1, &2]);
+        preferences.insert(&1, vec![&0, &1, &2]);
+        preferences.insert(&2, vec![&0, &1, &2]);
+        preferences.insert(&3, vec![&0, &1, &2]);
+
+        let mut partners = HashMap::new();
+        partners.insert(&0, &2);
+        partners.insert(&1, &0);
+        partners.insert(&2, &2);
+
+        let side = compute_side(&preferences, &partners);
+        assert_eq!(side.ranks.get(&0), Some(&2));
+        assert_eq!(side.ranks.get(&1), Some(&0));
+        assert_eq!(side.ranks.get(&3), None);
+        assert_eq!(side.total_rank, 4);
+        assert!((side.average_rank - 4.0 / 3.0).abs() < 1e-9);
+        assert_eq!(side.first_choices, 1);
+        assert_eq!(side.rank_histogram, vec![1, 0, 2]);
+        assert_eq!(side.unmatched, 1);
+    }
+
+    #[test]
+    fn test_compute_empty_matching() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&0]);
+
+        let metrics = compute(&men_preferences, &women_preferences, &HashMap::new());
+        assert_eq!(metrics.men.unmatched, 1);
+        assert_eq!(metrics.women.unmatched, 1);
+        assert_eq!(metrics.men.average_rank, 0.0);
+        assert_eq!(metrics.egalitarian_cost, 0);
+        assert_eq!(metrics.regret, 0);
+    }
+}
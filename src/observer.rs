@@ -0,0 +1,19 @@
+/**
+    Receives the events of a Gale-Shapley run. See `gale_shapley_with_observer`
+
+    Every method does nothing by default, so implement only the ones you care about. All the
+    events are given as (man, woman).
+*/
+pub trait Observer<T> {
+    /// `man` proposed to `woman`
+    fn on_proposal(&mut self, _man: &T, _woman: &T) {}
+
+    /// `woman` tentatively accepted the proposal of `man`. They are now engaged
+    fn on_accept(&mut self, _man: &T, _woman: &T) {}
+
+    /// `woman` turned down the proposal of `man`
+    fn on_reject(&mut self, _man: &T, _woman: &T) {}
+
+    /// `woman` broke off her engagement with `man` because she accepted a better proposal
+    fn on_break_engagement(&mut self, _man: &T, _woman: &T) {}
+}
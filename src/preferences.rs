@@ -0,0 +1,412 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::Algorithm;
+
+/**
+    Anything that can tell what an agent wants, without necessarily holding a list per agent
+
+    `Solver::solve_with_preferences` only asks proposers for their `next_choice`, and receivers to
+    compare (`prefers`) and screen (`is_acceptable`) the proposers. A side can therefore compute
+    choices on the fly, or compare with a closure (see `Comparator`).
+
+    `HashMap<&T, Vec<&T>>`, the shape every other function of this crate takes, implements it.
+    Its implementation looks agents up in the lists, which takes linear time, so for plain lists
+    `gale_shapley` is faster.
+*/
+pub trait Preferences<A> {
+    /// The choice that comes right after `after` in `agent`'s preferences, or the first choice if
+    /// `after` is `None`. `None` once there is nobody left
+    fn next_choice(&self, agent: A, after: Option<A>) -> Option<A>;
+
+    /// `Ordering::Less` if `agent` prefers `a` to `b`, `Ordering::Greater` if they prefer `b` to `a`.
+    /// Same order as the preference list, best first
+    fn prefers(&self, agent: A, a: A, b: A) -> Ordering;
+
+    /// Returns true if `agent` would rather be matched with `other` than single. Everyone is acceptable unless overridden
+    fn is_acceptable(&self, _agent: A, _other: A) -> bool {
+        return true;
+    }
+}
+
+impl<'a, T> Preferences<&'a T> for HashMap<&'a T, Vec<&'a T>>
+where
+    T: Eq + Hash,
+{
+    fn next_choice(&self, agent: &'a T, after: Option<&'a T>) -> Option<&'a T> {
+        let choices = self.get(&agent)?;
+        let position = match after {
+            Some(after) => choices.iter().position(|choice| *choice == after)? + 1,
+            None => 0,
+        };
+
+        return choices.get(position).cloned();
+    }
+
+    fn prefers(&self, agent: &'a T, a: &'a T, b: &'a T) -> Ordering {
+        let choices = match self.get(&agent) {
+            Some(choices) => choices,
+            None => return Ordering::Equal,
+        };
+        // Agents who are not on the list come after everyone who is
+        let rank = |other: &'a T| choices.iter().position(|choice| *choice == other);
+
+        return rank(a)
+            .unwrap_or(usize::MAX)
+            .cmp(&rank(b).unwrap_or(usize::MAX));
+    }
+
+    fn is_acceptable(&self, agent: &'a T, other: &'a T) -> bool {
+        return match self.get(&agent) {
+            Some(choices) => choices.contains(&other),
+            None => false,
+        };
+    }
+}
+
+/**
+    Receiver preferences given by a closure: `compare(agent, a, b)` is what `Preferences::prefers` returns
+
+    A `Comparator` never proposes, so it can only be used for the women.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::{Comparator, Solver};
+
+    let mut men_preferences = HashMap::new();
+    men_preferences.insert(&"octavian", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    // Both women prefer the man with the shorter name
+    let women_preferences =
+        Comparator(|_woman: &&str, a: &&str, b: &&str| a.len().cmp(&b.len()));
+
+    let engaged_man_woman = Solver::new().solve_with_preferences(
+        men_preferences.keys().cloned(),
+        &men_preferences,
+        &women_preferences,
+    );
+
+    assert_eq!(engaged_man_woman.get(&"antony"), Some(&&"cleopatra"));
+    assert_eq!(engaged_man_woman.get(&"octavian"), Some(&&"boudica"));
+    ```
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct Comparator<F>(pub F);
+
+impl<A, F> Preferences<A> for Comparator<F>
+where
+    F: Fn(A, A, A) -> Ordering,
+{
+    fn next_choice(&self, _agent: A, _after: Option<A>) -> Option<A> {
+        return None;
+    }
+
+    fn prefers(&self, agent: A, a: A, b: A) -> Ordering {
+        return (self.0)(agent, a, b);
+    }
+}
+
+/**
+    Proposer preferences pulled from iterators, one choice at a time, only when they are needed
+
+    `make(agent)` creates the iterator of `agent`'s choices, best first, the first time `agent` is
+    asked for one. Choices that have been pulled are remembered, so memory grows with the number of
+    proposals rather than with the size of the market. `prefers` and `is_acceptable` keep pulling
+    until they find the agents they are asked about, but the solver never calls them on the men.
+
+    # Examples
+    ```
+    use matchertools::{Comparator, Solver, Streams};
+
+    let n: u32 = 100_000;
+
+    // Man i likes women i, i + 1, i + 2, ... best. Women like men with a lower number better
+    let men_preferences = Streams::new(|man: u32| (0..n).map(move |k| (man + k) % n));
+    let women_preferences = Comparator(|_woman: u32, a: u32, b: u32| a.cmp(&b));
+
+    let engaged_man_woman =
+        Solver::new().solve_with_preferences(0..n, &men_preferences, &women_preferences);
+
+    assert_eq!(engaged_man_woman.get(&42), Some(&42));
+    // Everyone got their first choice: one choice was pulled per man, instead of n per man
+    assert_eq!(men_preferences.pulled(), n as usize);
+    ```
+*/
+pub struct Streams<A, I, F> {
+    make: F,
+    streams: RefCell<HashMap<A, Stream<A, I>>>,
+}
+
+struct Stream<A, I> {
+    choices: I,
+    pulled: Vec<A>,
+}
+
+impl<A, I, F> Streams<A, I, F>
+where
+    A: Copy + Eq + Hash,
+    I: Iterator<Item = A>,
+    F: Fn(A) -> I,
+{
+    /// Creates the streams. `make` is only called once per agent, when they are first asked for a choice
+    pub fn new(make: F) -> Streams<A, I, F> {
+        return Streams {
+            make,
+            streams: RefCell::new(HashMap::new()),
+        };
+    }
+
+    /// Total number of choices pulled from all the streams so far
+    pub fn pulled(&self) -> usize {
+        return self
+            .streams
+            .borrow()
+            .values()
+            .map(|stream| stream.pulled.len())
+            .sum();
+    }
+
+    /// Pulls `agent`'s choices until `done` returns true or there are no more, and returns what `done` returned
+    fn pull_until<R>(&self, agent: A, done: impl Fn(&[A]) -> Option<R>) -> Option<R> {
+        let mut streams = self.streams.borrow_mut();
+        let stream = streams.entry(agent).or_insert_with(|| Stream {
+            choices: (self.make)(agent),
+            pulled: Vec::new(),
+        });
+
+        loop {
+            if let Some(result) = done(&stream.pulled) {
+                return Some(result);
+            }
+            match stream.choices.next() {
+                Some(choice) => stream.pulled.push(choice),
+                None => return None,
+            }
+        }
+    }
+}
+
+impl<A, I, F> Preferences<A> for Streams<A, I, F>
+where
+    A: Copy + Eq + Hash,
+    I: Iterator<Item = A>,
+    F: Fn(A) -> I,
+{
+    fn next_choice(&self, agent: A, after: Option<A>) -> Option<A> {
+        return self.pull_until(agent, |pulled| {
+            // the solver always asks for the choice after the last one, so look from the end
+            let position = match after {
+                Some(after) => pulled.iter().rposition(|choice| *choice == after)? + 1,
+                None => 0,
+            };
+            return pulled.get(position).cloned();
+        });
+    }
+
+    fn prefers(&self, agent: A, a: A, b: A) -> Ordering {
+        let found = self.pull_until(agent, |pulled| {
+            return pulled
+                .iter()
+                .find(|choice| **choice == a || **choice == b)
+                .cloned();
+        });
+
+        return match found {
+            _ if a == b => Ordering::Equal,
+            Some(first) if first == a => Ordering::Less,
+            Some(_) => Ordering::Greater,
+            None => Ordering::Equal,
+        };
+    }
+
+    fn is_acceptable(&self, agent: A, other: A) -> bool {
+        return self
+            .pull_until(agent, |pulled| {
+                pulled.iter().find(|choice| **choice == other).cloned()
+            })
+            .is_some();
+    }
+}
+
+/// Gale-Shapley over `Preferences`, following the same `algorithm` as the list-based engines
+pub(crate) fn solve_preferences<A, M, W>(
+    algorithm: Algorithm,
+    men: Vec<A>,
+    men_preferences: &M,
+    women_preferences: &W,
+) -> HashMap<A, A>
+where
+    A: Copy + Eq + Hash,
+    M: Preferences<A> + ?Sized,
+    W: Preferences<A> + ?Sized,
+{
+    // the last woman each man proposed to
+    let mut last_choice: HashMap<A, A> = HashMap::new();
+    let mut engaged_woman_man: HashMap<A, A> = HashMap::new();
+
+    let propose = |man: A, last_choice: &mut HashMap<A, A>| -> Option<A> {
+        let woman = men_preferences.next_choice(man, last_choice.get(&man).cloned())?;
+        last_choice.insert(man, woman);
+        return Some(woman);
+    };
+
+    match algorithm {
+        Algorithm::Rounds => {
+            let mut unengaged_men = men;
+            while !unengaged_men.is_empty() {
+                let mut proposals: HashMap<A, Vec<A>> = HashMap::new();
+                for man in unengaged_men.drain(..) {
+                    if let Some(woman) = propose(man, &mut last_choice) {
+                        proposals.entry(woman).or_default().push(man);
+                    }
+                }
+
+                for (woman, proposers) in proposals {
+                    let (acceptable, unacceptable): (Vec<A>, Vec<A>) = proposers
+                        .into_iter()
+                        .partition(|man| women_preferences.is_acceptable(woman, *man));
+                    // men she does not accept move on to their next choice
+                    unengaged_men.extend(unacceptable);
+
+                    // the current partner goes first, so that she keeps him if she likes someone new just as much
+                    let mut candidates: Vec<A> =
+                        engaged_woman_man.get(&woman).cloned().into_iter().collect();
+                    candidates.extend(acceptable);
+                    let best = match candidates
+                        .iter()
+                        .min_by(|a, b| women_preferences.prefers(woman, **a, **b))
+                    {
+                        Some(best) => *best,
+                        None => continue,
+                    };
+
+                    engaged_woman_man.insert(woman, best);
+                    unengaged_men.extend(candidates.into_iter().filter(|man| *man != best));
+                }
+            }
+        }
+        Algorithm::Sequential => {
+            for man in men {
+                let mut proposer = man;
+                while let Some(woman) = propose(proposer, &mut last_choice) {
+                    if !women_preferences.is_acceptable(woman, proposer) {
+                        continue;
+                    }
+
+                    match engaged_woman_man.get(&woman).cloned() {
+                        None => {
+                            engaged_woman_man.insert(woman, proposer);
+                            break;
+                        }
+                        Some(current_man) => {
+                            if women_preferences.prefers(woman, proposer, current_man)
+                                == Ordering::Less
+                            {
+                                engaged_woman_man.insert(woman, proposer);
+                                proposer = current_man;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    return engaged_woman_man
+        .into_iter()
+        .map(|(woman, man)| (man, woman))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_preferences() -> HashMap<&'static u32, Vec<&'static u32>> {
+        let mut preferences = HashMap::new();
+        preferences.insert(&0, vec![&2, &0]);
+        return preferences;
+    }
+
+    #[test]
+    fn test_hashmap_next_choice() {
+        let preferences = get_preferences();
+
+        assert_eq!(preferences.next_choice(&0, None), Some(&2));
+        assert_eq!(preferences.next_choice(&0, Some(&2)), Some(&0));
+        assert_eq!(preferences.next_choice(&0, Some(&0)), None);
+        assert_eq!(preferences.next_choice(&0, Some(&1)), None);
+        assert_eq!(preferences.next_choice(&1, None), None);
+    }
+
+    #[test]
+    fn test_hashmap_prefers() {
+        let preferences = get_preferences();
+
+        assert_eq!(preferences.prefers(&0, &2, &0), Ordering::Less);
+        assert_eq!(preferences.prefers(&0, &0, &2), Ordering::Greater);
+        assert_eq!(preferences.prefers(&0, &0, &1), Ordering::Less);
+        assert!(preferences.is_acceptable(&0, &2));
+        assert!(!preferences.is_acceptable(&0, &1));
+    }
+
+    #[test]
+    fn test_both_algorithms_match_lists() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1, &2]);
+        men_preferences.insert(&1, vec![&0, &2, &1]);
+        men_preferences.insert(&2, vec![&0, &1]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&2, &1, &0]);
+        women_preferences.insert(&1, vec![&0, &1, &2]);
+        women_preferences.insert(&2, vec![&1, &0]);
+
+        let expected = crate::gale_shapley(&men_preferences, &women_preferences);
+        for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+            let men: Vec<&u32> = men_preferences.keys().cloned().collect();
+            let engaged_man_woman =
+                solve_preferences(*algorithm, men, &men_preferences, &women_preferences);
+            assert_eq!(engaged_man_woman, expected);
+        }
+    }
+
+    #[test]
+    fn test_unacceptable_proposers_move_on() {
+        // boudica is everyone's first choice, but she only lists julius
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&"julius", vec![&"boudica", &"cleopatra"]);
+        men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&"boudica", vec![&"julius"]);
+        women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+
+        let expected = crate::gale_shapley(&men_preferences, &women_preferences);
+        assert_eq!(expected.get(&"antony"), Some(&&"cleopatra"));
+        for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+            let men: Vec<&&str> = men_preferences.keys().cloned().collect();
+            let engaged_man_woman =
+                solve_preferences(*algorithm, men, &men_preferences, &women_preferences);
+            assert_eq!(engaged_man_woman, expected);
+        }
+    }
+
+    #[test]
+    fn test_streams_pull_lazily() {
+        let streams = Streams::new(|agent: u32| vec![agent, 7, 3].into_iter());
+
+        assert_eq!(streams.next_choice(1, None), Some(1));
+        assert_eq!(streams.pulled(), 1);
+        assert_eq!(streams.next_choice(1, Some(1)), Some(7));
+        assert_eq!(streams.next_choice(1, Some(3)), None);
+        assert_eq!(streams.pulled(), 3);
+
+        assert_eq!(streams.prefers(2, 7, 2), Ordering::Greater);
+        assert_eq!(streams.pulled(), 4);
+        assert!(streams.is_acceptable(2, 3));
+        assert!(!streams.is_acceptable(2, 9));
+    }
+}
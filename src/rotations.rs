@@ -0,0 +1,472 @@
+//! Rotations, and the things that can be done with them
+//!
+//! Go read "The Stable Marriage Problem: Structure and Algorithms" by Gusfield and Irving. In short: a
+//! rotation is a cycle of men (m_0, w_0), ..., (m_{r-1}, w_{r-1}) in a stable matching where every m_i
+//! can move on to w_{i+1} and the result is still stable. Starting from the man-optimal matching, every
+//! stable matching is obtained by eliminating a set of rotations that is closed under the precedence
+//! order between rotations, and every closed set gives a stable matching.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+use crate::{gale_shapley_internal, Interned};
+
+/// A rotation. `pairs[i]` is (m_i, w_i) and m_i moves on to w_{i+1} when the rotation is eliminated
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Rotation {
+    pub(crate) pairs: Vec<(u32, u32)>,
+}
+
+impl Rotation {
+    /// The (man, woman) pairs that eliminating this rotation creates
+    pub(crate) fn produced_pairs(&self) -> Vec<(u32, u32)> {
+        let length = self.pairs.len();
+        return (0..length)
+            .map(|i| (self.pairs[i].0, self.pairs[(i + 1) % length].1))
+            .collect();
+    }
+}
+
+/// Every rotation of an instance, along with the precedence order between them
+pub(crate) struct RotationPoset {
+    pub(crate) man_optimal: HashMap<u32, u32>,
+    /// In the order they were eliminated on the way to the woman-optimal matching. That order
+    /// respects `predecessors`
+    pub(crate) rotations: Vec<Rotation>,
+    /// `predecessors[k]` holds (some of) the rotations that have to be eliminated before rotation k.
+    /// Their transitive closure is the precedence order
+    pub(crate) predecessors: Vec<HashSet<usize>>,
+}
+
+impl RotationPoset {
+    pub(crate) fn new(
+        men_preferences: &HashMap<u32, Vec<u32>>,
+        women_preferences: &HashMap<u32, Vec<u32>>,
+    ) -> RotationPoset {
+        let man_optimal = gale_shapley_internal(men_preferences, women_preferences, None);
+        let woman_optimal: HashMap<u32, u32> =
+            gale_shapley_internal(women_preferences, men_preferences, None)
+                .into_iter()
+                .map(|(woman, man)| (man, woman))
+                .collect();
+        let women_ranks = rank_tables(women_preferences);
+
+        let rotations = find_rotations(men_preferences, &women_ranks, &man_optimal, &woman_optimal);
+        let predecessors =
+            find_predecessors(men_preferences, &women_ranks, &man_optimal, &rotations);
+
+        return RotationPoset {
+            man_optimal,
+            rotations,
+            predecessors,
+        };
+    }
+
+    /// The stable matching obtained by eliminating `eliminated` from the man-optimal matching. `eliminated` must be closed
+    pub(crate) fn matching(&self, eliminated: &HashSet<usize>) -> HashMap<u32, u32> {
+        let mut engaged_man_woman = self.man_optimal.clone();
+        for (index, rotation) in self.rotations.iter().enumerate() {
+            if eliminated.contains(&index) {
+                for (man, woman) in rotation.produced_pairs() {
+                    engaged_man_woman.insert(man, woman);
+                }
+            }
+        }
+
+        return engaged_man_woman;
+    }
+}
+
+/// preferences -> for each agent, the rank of everyone on their list
+pub(crate) fn rank_tables(
+    preferences: &HashMap<u32, Vec<u32>>,
+) -> HashMap<u32, HashMap<u32, usize>> {
+    return preferences
+        .iter()
+        .map(|(agent, list)| {
+            let ranks = list
+                .iter()
+                .enumerate()
+                .map(|(rank, other)| (*other, rank))
+                .collect();
+            (*agent, ranks)
+        })
+        .collect();
+}
+
+/// Eliminates exposed rotations one at a time, from the man-optimal matching down to the woman-optimal one
+fn find_rotations(
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_ranks: &HashMap<u32, HashMap<u32, usize>>,
+    man_optimal: &HashMap<u32, u32>,
+    woman_optimal: &HashMap<u32, u32>,
+) -> Vec<Rotation> {
+    let mut rotations: Vec<Rotation> = Vec::new();
+    let mut engaged_man_woman = man_optimal.clone();
+    let mut engaged_woman_man: HashMap<u32, u32> = man_optimal
+        .iter()
+        .map(|(man, woman)| (*woman, *man))
+        .collect();
+
+    let mut men: Vec<u32> = man_optimal.keys().cloned().collect();
+    men.sort_unstable();
+
+    // The next woman down a man's list who would take him over her partner
+    let next_woman = |man: u32,
+                      engaged_man_woman: &HashMap<u32, u32>,
+                      engaged_woman_man: &HashMap<u32, u32>|
+     -> u32 {
+        let preferences = men_preferences.get(&man).unwrap();
+        let current = engaged_man_woman.get(&man).unwrap();
+        let position = preferences
+            .iter()
+            .position(|woman| woman == current)
+            .unwrap();
+        for woman in &preferences[position + 1..] {
+            let ranks = match women_ranks.get(woman) {
+                Some(ranks) => ranks,
+                None => continue,
+            };
+            let partner = match engaged_woman_man.get(woman) {
+                Some(partner) => partner,
+                None => continue,
+            };
+            if let Some(rank_man) = ranks.get(&man) {
+                if rank_man < ranks.get(partner).unwrap() {
+                    return *woman;
+                }
+            }
+        }
+
+        panic!("A man who is not with his woman-optimal partner must have a next woman");
+    };
+
+    for start in men {
+        while engaged_man_woman.get(&start) != woman_optimal.get(&start) {
+            // Follow next(m) = partner of next_woman(m) until a man repeats. The cycle is an exposed rotation
+            let mut sequence: Vec<u32> = Vec::new();
+            let mut position_in_sequence: HashMap<u32, usize> = HashMap::new();
+            let mut man = start;
+            while !position_in_sequence.contains_key(&man) {
+                position_in_sequence.insert(man, sequence.len());
+                sequence.push(man);
+                let woman = next_woman(man, &engaged_man_woman, &engaged_woman_man);
+                man = *engaged_woman_man.get(&woman).unwrap();
+            }
+
+            let cycle = &sequence[*position_in_sequence.get(&man).unwrap()..];
+            let rotation = Rotation {
+                pairs: cycle
+                    .iter()
+                    .map(|man| (*man, *engaged_man_woman.get(man).unwrap()))
+                    .collect(),
+            };
+            for (man, woman) in rotation.produced_pairs() {
+                engaged_man_woman.insert(man, woman);
+                engaged_woman_man.insert(woman, man);
+            }
+            rotations.push(rotation);
+        }
+    }
+
+    return rotations;
+}
+
+fn find_predecessors(
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_ranks: &HashMap<u32, HashMap<u32, usize>>,
+    man_optimal: &HashMap<u32, u32>,
+    rotations: &[Rotation],
+) -> Vec<HashSet<usize>> {
+    // woman -> her partners, from the man-optimal matching on, with the rotation that gave her each one
+    let mut partner_history: HashMap<u32, Vec<(u32, Option<usize>)>> = HashMap::new();
+    for (man, woman) in man_optimal {
+        partner_history.insert(*woman, vec![(*man, None)]);
+    }
+    let mut produced_by: HashMap<(u32, u32), usize> = HashMap::new();
+    for (index, rotation) in rotations.iter().enumerate() {
+        for (man, woman) in rotation.produced_pairs() {
+            produced_by.insert((man, woman), index);
+            partner_history
+                .get_mut(&woman)
+                .unwrap()
+                .push((man, Some(index)));
+        }
+    }
+
+    let mut predecessors: Vec<HashSet<usize>> = vec![HashSet::new(); rotations.len()];
+    for (index, rotation) in rotations.iter().enumerate() {
+        let produced_pairs = rotation.produced_pairs();
+        for (i, (man, woman)) in rotation.pairs.iter().enumerate() {
+            // Rule 1: the rotation that gave this man this woman comes first
+            if let Some(producer) = produced_by.get(&(*man, *woman)) {
+                predecessors[index].insert(*producer);
+            }
+
+            // Rule 2: for every woman the man skips, the rotation that got her someone better than him comes first
+            let preferences = men_preferences.get(man).unwrap();
+            let from = preferences.iter().position(|w| w == woman).unwrap();
+            let to = preferences
+                .iter()
+                .position(|w| *w == produced_pairs[i].1)
+                .unwrap();
+            for skipped in &preferences[from + 1..to] {
+                let rank_man = match women_ranks.get(skipped).and_then(|ranks| ranks.get(man)) {
+                    Some(rank_man) => *rank_man,
+                    None => continue,
+                };
+                let history = match partner_history.get(skipped) {
+                    Some(history) => history,
+                    None => continue,
+                };
+                let ranks = women_ranks.get(skipped).unwrap();
+                for pair in history.windows(2) {
+                    let worse = *ranks.get(&pair[0].0).unwrap() > rank_man;
+                    let better = *ranks.get(&pair[1].0).unwrap() < rank_man;
+                    if worse && better {
+                        predecessors[index].insert(pair[1].1.unwrap());
+                    }
+                }
+            }
+        }
+    }
+
+    return predecessors;
+}
+
+/// Returns the closed set of rotations with the largest total weight, via a minimum cut (Picard, 1976)
+///
+/// Of all the closed sets with the largest weight, this returns the smallest one.
+pub(crate) fn maximum_weight_closure(
+    weights: &[i64],
+    predecessors: &[HashSet<usize>],
+) -> HashSet<usize> {
+    let count = weights.len();
+    let source = count;
+    let sink = count + 1;
+    let infinity: i64 = weights.iter().map(|weight| weight.abs()).sum::<i64>() + 1;
+
+    let mut network = FlowNetwork::new(count + 2);
+    for (node, weight) in weights.iter().enumerate() {
+        if *weight > 0 {
+            network.add_edge(source, node, *weight);
+        } else if *weight < 0 {
+            network.add_edge(node, sink, -*weight);
+        }
+        for predecessor in &predecessors[node] {
+            network.add_edge(node, *predecessor, infinity);
+        }
+    }
+
+    network.maximum_flow(source, sink);
+    return network
+        .reachable_from(source)
+        .into_iter()
+        .filter(|node| *node < count)
+        .collect();
+}
+
+/// Edmonds-Karp, on an adjacency list with paired reverse edges
+struct FlowNetwork {
+    adjacency: Vec<Vec<usize>>,
+    /// (to, residual capacity). Edge e and e ^ 1 are the reverse of each other
+    edges: Vec<(usize, i64)>,
+}
+
+impl FlowNetwork {
+    fn new(nodes: usize) -> FlowNetwork {
+        return FlowNetwork {
+            adjacency: vec![Vec::new(); nodes],
+            edges: Vec::new(),
+        };
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize, capacity: i64) {
+        self.adjacency[from].push(self.edges.len());
+        self.edges.push((to, capacity));
+        self.adjacency[to].push(self.edges.len());
+        self.edges.push((from, 0));
+    }
+
+    fn maximum_flow(&mut self, source: usize, sink: usize) -> i64 {
+        let mut flow = 0;
+        loop {
+            // Breadth first search for the shortest augmenting path
+            let mut via_edge: Vec<Option<usize>> = vec![None; self.adjacency.len()];
+            let mut queue: VecDeque<usize> = VecDeque::new();
+            queue.push_back(source);
+            while let Some(node) = queue.pop_front() {
+                for edge in &self.adjacency[node] {
+                    let (to, capacity) = self.edges[*edge];
+                    if capacity > 0 && to != source && via_edge[to].is_none() {
+                        via_edge[to] = Some(*edge);
+                        queue.push_back(to);
+                    }
+                }
+            }
+            if via_edge[sink].is_none() {
+                return flow;
+            }
+
+            let mut bottleneck = i64::MAX;
+            let mut node = sink;
+            while let Some(edge) = via_edge[node] {
+                bottleneck = std::cmp::min(bottleneck, self.edges[edge].1);
+                node = self.edges[edge ^ 1].0;
+            }
+            let mut node = sink;
+            while let Some(edge) = via_edge[node] {
+                self.edges[edge].1 -= bottleneck;
+                self.edges[edge ^ 1].1 += bottleneck;
+                node = self.edges[edge ^ 1].0;
+            }
+            flow += bottleneck;
+        }
+    }
+
+    fn reachable_from(&self, source: usize) -> HashSet<usize> {
+        let mut reachable: HashSet<usize> = HashSet::new();
+        let mut stack = vec![source];
+        reachable.insert(source);
+        while let Some(node) = stack.pop() {
+            for edge in &self.adjacency[node] {
+                let (to, capacity) = self.edges[*edge];
+                if capacity > 0 && reachable.insert(to) {
+                    stack.push(to);
+                }
+            }
+        }
+
+        return reachable;
+    }
+}
+
+/**
+    Returns the stable matching that has the most pairs in common with `reference_engagements`
+
+    `reference_engagements` maps men to women and does not have to be stable or complete. Think of
+    last week's assignment, before some preferences were corrected. Since every stable matching
+    matches the same people, the returned matching is also the one with the fewest pairs that
+    differ from the reference. Use `diff::compare` to see what changed.
+
+    Each rotation is weighed by the reference pairs it creates minus the ones it breaks up. The best
+    stable matching comes from the closed set of rotations with the largest weight, which is found
+    with a minimum cut. If there are several, the one closest to the man-optimal matching is returned.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"boudica", &"cleopatra"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    // Both ways of pairing them up are stable
+    let mut reference = HashMap::new();
+    reference.insert(&"julius", &"boudica");
+
+    let engaged_man_woman =
+        matchertools::closest_stable_matching(&men_preferences, &women_preferences, &reference);
+
+    assert_eq!(engaged_man_woman.get(&&"julius"), Some(&&"boudica"));
+    assert_eq!(engaged_man_woman.get(&&"antony"), Some(&&"cleopatra"));
+    ```
+*/
+pub fn closest_stable_matching<'a, T>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    reference_engagements: &HashMap<&T, &T>,
+) -> HashMap<&'a T, &'a T>
+where
+    T: Eq + Hash,
+{
+    let interned = Interned::new(input_men_preferences, input_women_preferences);
+    let reference = interned.engagements_to_u32(reference_engagements);
+
+    let poset = RotationPoset::new(&interned.men_preferences, &interned.women_preferences);
+    let in_reference = |(man, woman): &(u32, u32)| reference.get(man) == Some(woman);
+    let weights: Vec<i64> = poset
+        .rotations
+        .iter()
+        .map(|rotation| {
+            let created = rotation
+                .produced_pairs()
+                .iter()
+                .filter(|pair| in_reference(pair))
+                .count();
+            let broken = rotation
+                .pairs
+                .iter()
+                .filter(|pair| in_reference(pair))
+                .count();
+            created as i64 - broken as i64
+        })
+        .collect();
+
+    let eliminated = maximum_weight_closure(&weights, &poset.predecessors);
+
+    return interned.engagements_from_u32(&poset.matching(&eliminated));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The instance with 3 men and women where everyone's first choice likes them least
+    fn get_latin_square() -> (HashMap<u32, Vec<u32>>, HashMap<u32, Vec<u32>>) {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(0, vec![0, 1, 2]);
+        men_preferences.insert(1, vec![1, 2, 0]);
+        men_preferences.insert(2, vec![2, 0, 1]);
+
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(0, vec![1, 2, 0]);
+        women_preferences.insert(1, vec![2, 0, 1]);
+        women_preferences.insert(2, vec![0, 1, 2]);
+
+        return (men_preferences, women_preferences);
+    }
+
+    #[test]
+    fn test_rotations_latin_square() {
+        let (men_preferences, women_preferences) = get_latin_square();
+        let poset = RotationPoset::new(&men_preferences, &women_preferences);
+
+        // man i gets woman i, then woman i + 1, then woman i + 2
+        assert_eq!(poset.rotations.len(), 2);
+        assert_eq!(poset.rotations[0].pairs, vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(poset.rotations[1].pairs, vec![(0, 1), (1, 2), (2, 0)]);
+        assert!(poset.predecessors[1].contains(&0));
+
+        let all: HashSet<usize> = vec![0, 1].into_iter().collect();
+        let woman_optimal = poset.matching(&all);
+        assert_eq!(woman_optimal.get(&0), Some(&2));
+        assert_eq!(woman_optimal.get(&1), Some(&0));
+    }
+
+    #[test]
+    fn test_maximum_weight_closure() {
+        // 0 <- 1 <- 2, and 3 on its own
+        let predecessors: Vec<HashSet<usize>> = vec![
+            HashSet::new(),
+            vec![0].into_iter().collect(),
+            vec![1].into_iter().collect(),
+            HashSet::new(),
+        ];
+
+        let closure = maximum_weight_closure(&[-1, -1, 3, -2], &predecessors);
+        assert_eq!(closure, vec![0, 1, 2].into_iter().collect());
+
+        let closure = maximum_weight_closure(&[-1, -1, 1, 2], &predecessors);
+        assert_eq!(closure, vec![3].into_iter().collect());
+
+        // Taking 0 and 1 gains nothing, so the smaller closure wins
+        let closure = maximum_weight_closure(&[-1, 1, 0, 0], &predecessors);
+        assert_eq!(closure, HashSet::new());
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::{get_rank, get_unengaged_men, play_round, Checkpoint, CheckpointError, Interned};
+
+/**
+    A Gale-Shapley run that can be advanced one round at a time.
+
+    Every call to `next()` lets all the un-engaged men propose to their most preferred woman
+    who has not rejected them yet, and returns a `Round` describing what happened. The iterator
+    ends once no un-engaged man has anyone left to propose to, so with incomplete lists some men
+    may stay single. At that point `engagements()` is the same stable matching that `gale_shapley`
+    would have returned.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::GaleShapleyRun;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let mut run = GaleShapleyRun::new(&men_preferences, &women_preferences);
+
+    let first_round = run.next().unwrap();
+    assert_eq!(first_round.number, 1);
+    assert_eq!(first_round.accepted, vec![(&"antony", &"cleopatra")]);
+    assert_eq!(first_round.rejected, vec![(&"julius", &"cleopatra")]);
+
+    let second_round = run.next().unwrap();
+    assert_eq!(second_round.accepted, vec![(&"julius", &"boudica")]);
+
+    assert!(run.next().is_none());
+    assert_eq!(run.engagements().get(&"julius"), Some(&&"boudica"));
+    ```
+*/
+pub struct GaleShapleyRun<'a, T> {
+    interned: Interned<'a, T>,
+    engaged_man_woman: HashMap<u32, u32>,
+    rounds_played: usize,
+    /// Length of each man's list before any proposal, indexed by man
+    list_lengths: Vec<usize>,
+}
+
+/// Everything that happened in a single round of a `GaleShapleyRun`
+///
+/// All the pairs are (man, woman). The order of the pairs within a vec is unspecified. More fields may
+/// be added, so a `Round` can only be built by a `GaleShapleyRun`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Round<'a, T> {
+    /// The round number, starting at 1
+    pub number: usize,
+    /// Every proposal made in this round
+    pub proposals: Vec<(&'a T, &'a T)>,
+    /// The proposals that were tentatively accepted. The woman may still leave the man in a later round
+    pub accepted: Vec<(&'a T, &'a T)>,
+    /// The proposals that were turned down. A man never proposes to the same woman again
+    pub rejected: Vec<(&'a T, &'a T)>,
+    /// The engagements that were broken off because the woman accepted a better proposal
+    pub broken_engagements: Vec<(&'a T, &'a T)>,
+    /// The tentative engagements at the end of this round
+    pub engagements: HashMap<&'a T, &'a T>,
+}
+
+impl<'a, T> GaleShapleyRun<'a, T>
+where
+    T: Eq + Hash,
+{
+    /// Prepares a run over the same input that `gale_shapley` accepts. No proposals are made until `next()` is called
+    pub fn new(
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    ) -> GaleShapleyRun<'a, T> {
+        let interned = Interned::new(input_men_preferences, input_women_preferences);
+        let list_lengths = (0..interned.men.len() as u32)
+            .map(|man| interned.men_preferences.get(&man).unwrap().len())
+            .collect();
+
+        return GaleShapleyRun {
+            interned,
+            engaged_man_woman: HashMap::new(),
+            rounds_played: 0,
+            list_lengths,
+        };
+    }
+
+    /**
+        Carries on from a checkpoint of a run on the same preferences
+
+        Playing the rest of the rounds gives the same rounds, and the same matching, as the run the
+        checkpoint was taken from. The checkpoint is checked against the preferences first: it fails
+        if it names an agent who is not in them, has a man propose to more women than his list has,
+        has an engagement other than each man's last proposal, or has a woman engaged to a man she
+        does not list.
+    */
+    pub fn resume(
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+        checkpoint: &Checkpoint<T>,
+    ) -> Result<GaleShapleyRun<'a, T>, CheckpointError<T>>
+    where
+        T: Clone,
+    {
+        let mut run = GaleShapleyRun::new(input_men_preferences, input_women_preferences);
+        let interned = &run.interned;
+        let man_id = |man: &T| -> Result<u32, CheckpointError<T>> {
+            return interned
+                .men_reference_to_u32
+                .get(man)
+                .cloned()
+                .ok_or_else(|| CheckpointError::UnknownMan(man.clone()));
+        };
+
+        let mut proposals: Vec<usize> = vec![0; interned.men.len()];
+        for (man, count) in &checkpoint.proposals {
+            let id = man_id(man)? as usize;
+            if *count > run.list_lengths[id] {
+                return Err(CheckpointError::TooManyProposals {
+                    man: man.clone(),
+                    proposals: *count,
+                    list_length: run.list_lengths[id],
+                });
+            }
+            proposals[id] = *count;
+        }
+
+        let mut engaged_man_woman: HashMap<u32, u32> = HashMap::new();
+        let mut engaged_women: HashSet<u32> = HashSet::new();
+        for (man, woman) in &checkpoint.engagements {
+            let man_id = man_id(man)?;
+            let woman_id = *interned
+                .women_reference_to_u32
+                .get(woman)
+                .ok_or_else(|| CheckpointError::UnknownWoman(woman.clone()))?;
+
+            // a man is only ever engaged to the last woman he proposed to
+            let choices = interned.men_preferences.get(&man_id).unwrap();
+            let last_proposal = proposals[man_id as usize]
+                .checked_sub(1)
+                .map(|index| choices[index]);
+            if last_proposal != Some(woman_id) {
+                return Err(CheckpointError::InconsistentEngagement {
+                    man: man.clone(),
+                    woman: woman.clone(),
+                });
+            }
+            if get_rank(&interned.women_preferences, &woman_id, &man_id).is_none() {
+                return Err(CheckpointError::UnacceptableEngagement {
+                    man: man.clone(),
+                    woman: woman.clone(),
+                });
+            }
+            if !engaged_women.insert(woman_id) {
+                return Err(CheckpointError::EngagedTwice(woman.clone()));
+            }
+            engaged_man_woman.insert(man_id, woman_id);
+        }
+
+        for (man, count) in proposals.into_iter().enumerate() {
+            run.interned
+                .men_preferences
+                .get_mut(&(man as u32))
+                .unwrap()
+                .drain(..count);
+        }
+        run.engaged_man_woman = engaged_man_woman;
+        run.rounds_played = checkpoint.rounds_played;
+
+        return Ok(run);
+    }
+
+    /// The state of the run, to resume it later with `resume`. See `Checkpoint`
+    pub fn checkpoint(&self) -> Checkpoint<T>
+    where
+        T: Clone,
+    {
+        let proposals = self
+            .interned
+            .men
+            .iter()
+            .enumerate()
+            .filter_map(|(man, reference)| {
+                let remaining = self.interned.men_preferences.get(&(man as u32)).unwrap();
+                let proposals = self.list_lengths[man] - remaining.len();
+                if proposals == 0 {
+                    return None;
+                }
+                return Some(((*reference).clone(), proposals));
+            })
+            .collect();
+
+        return Checkpoint {
+            rounds_played: self.rounds_played,
+            proposals,
+            engagements: self
+                .engagements()
+                .into_iter()
+                .map(|(man, woman)| (man.clone(), woman.clone()))
+                .collect(),
+        };
+    }
+
+    /// Number of rounds played so far
+    pub fn rounds_played(&self) -> usize {
+        return self.rounds_played;
+    }
+
+    /// Returns true once no un-engaged man has anyone left to propose to, i.e when `next()` would return `None`
+    pub fn is_finished(&self) -> bool {
+        return get_unengaged_men(&self.interned.men_preferences, &self.engaged_man_woman)
+            .is_empty();
+    }
+
+    /// The current tentative engagements. This is the final, stable matching once the run is finished
+    pub fn engagements(&self) -> HashMap<&'a T, &'a T> {
+        return self.interned.engagements_from_u32(&self.engaged_man_woman);
+    }
+}
+
+impl<'a, T> Iterator for GaleShapleyRun<'a, T>
+where
+    T: Eq + Hash,
+{
+    type Item = Round<'a, T>;
+
+    fn next(&mut self) -> Option<Round<'a, T>> {
+        if self.is_finished() {
+            return None;
+        }
+
+        let outcome = play_round(
+            &mut self.interned.men_preferences,
+            &self.interned.women_preferences,
+            &mut self.engaged_man_woman,
+            1,
+            true,
+        );
+        self.rounds_played += 1;
+
+        let interned = &self.interned;
+        let to_pairs = |pairs: &Vec<(u32, u32)>| -> Vec<(&'a T, &'a T)> {
+            pairs
+                .iter()
+                .map(|(man, woman)| (interned.man(*man), interned.woman(*woman)))
+                .collect()
+        };
+
+        let mut proposals: Vec<(&T, &T)> = Vec::new();
+        for (woman, men) in &outcome.proposals {
+            for man in men {
+                proposals.push((interned.man(*man), interned.woman(*woman)));
+            }
+        }
+
+        return Some(Round {
+            number: self.rounds_played,
+            proposals,
+            accepted: to_pairs(&outcome.accepted),
+            rejected: to_pairs(&outcome.rejected),
+            broken_engagements: to_pairs(&outcome.broken),
+            engagements: self.engagements(),
+        });
+    }
+}
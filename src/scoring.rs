@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/**
+    Builds preference lists from compatibility scores instead of explicit lists
+
+    Each agent ranks the candidates by decreasing `score(agent, candidate)`. Candidates with the same
+    score keep the order they have in the `candidates` slice, so the result does not depend on
+    anything but the input. Scores that are NaN make the candidate unacceptable.
+
+    A `Scoring` builds one side of the market. Build the other side with its own `Scoring` and scoring
+    function. `gale_shapley` needs both sides to have the same type, so the agents of both sides
+    have to be, for example, names or ids.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::Scoring;
+
+    let ages: HashMap<&str, i32> =
+        vec![("julius", 55), ("antony", 39), ("cleopatra", 29), ("boudica", 30)]
+            .into_iter()
+            .collect();
+    let age_gap = |a: &&str, b: &&str| (ages[a] - ages[b]).abs() as f64;
+
+    let men = vec!["julius", "antony"];
+    let women = vec!["cleopatra", "boudica"];
+
+    // Men like a small age gap. Women only accept older men, and only their favourite one
+    let men_preferences = Scoring::new(|man: &&str, woman: &&str| -age_gap(man, woman))
+        .preferences(&men, &women);
+    let women_preferences = Scoring::new(|woman: &&str, man: &&str| (ages[man] - ages[woman]) as f64)
+        .threshold(0.0)
+        .top_k(1)
+        .preferences(&women, &men);
+
+    assert_eq!(men_preferences.get(&"julius").unwrap(), &vec![&"boudica", &"cleopatra"]);
+    assert_eq!(women_preferences.get(&"cleopatra").unwrap(), &vec![&"julius"]);
+
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+    assert_eq!(engaged_man_woman.get(&"julius"), Some(&&"boudica"));
+    ```
+*/
+#[derive(Debug, Clone)]
+pub struct Scoring<F> {
+    score: F,
+    threshold: Option<f64>,
+    top_k: Option<usize>,
+}
+
+impl<F> Scoring<F> {
+    /// Ranks candidates by `score`, higher is better. Every candidate is acceptable
+    pub fn new(score: F) -> Scoring<F> {
+        return Scoring {
+            score,
+            threshold: None,
+            top_k: None,
+        };
+    }
+
+    /// Candidates with a score below `threshold` are left out of the lists
+    pub fn threshold(mut self, threshold: f64) -> Scoring<F> {
+        self.threshold = Some(threshold);
+        return self;
+    }
+
+    /// Only the `k` best acceptable candidates are kept
+    pub fn top_k(mut self, k: usize) -> Scoring<F> {
+        self.top_k = Some(k);
+        return self;
+    }
+
+    /// Every agent's preferences over `candidates`
+    pub fn preferences<'a, P, R>(
+        &self,
+        agents: &'a [P],
+        candidates: &'a [R],
+    ) -> HashMap<&'a P, Vec<&'a R>>
+    where
+        P: Eq + Hash,
+        F: Fn(&P, &R) -> f64,
+    {
+        let mut preferences: HashMap<&P, Vec<&R>> = HashMap::new();
+
+        for agent in agents {
+            let mut scored: Vec<(f64, &R)> = candidates
+                .iter()
+                .map(|candidate| ((self.score)(agent, candidate), candidate))
+                .filter(|(score, _)| !score.is_nan())
+                .filter(|(score, _)| self.threshold.map_or(true, |threshold| *score >= threshold))
+                .collect();
+
+            // sort_by is stable, so equal scores keep the order of `candidates`
+            scored.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+            if let Some(k) = self.top_k {
+                scored.truncate(k);
+            }
+
+            preferences.insert(
+                agent,
+                scored.into_iter().map(|(_, candidate)| candidate).collect(),
+            );
+        }
+
+        return preferences;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ties_keep_candidate_order() {
+        let agents = vec![0];
+        let candidates = vec![3, 1, 2, 0];
+        let preferences = Scoring::new(|_: &u32, candidate: &u32| (candidate % 2) as f64)
+            .preferences(&agents, &candidates);
+
+        assert_eq!(preferences.get(&0).unwrap(), &vec![&3, &1, &2, &0]);
+    }
+
+    #[test]
+    fn test_threshold_and_top_k() {
+        let agents = vec![0, 1];
+        let candidates = vec![0, 1, 2, 3, 4];
+        let score = |agent: &u32, candidate: &u32| -> f64 {
+            if *candidate == 4 {
+                return f64::NAN;
+            }
+            return (agent + candidate) as f64;
+        };
+
+        let preferences = Scoring::new(score)
+            .threshold(2.0)
+            .preferences(&agents, &candidates);
+        assert_eq!(preferences.get(&0).unwrap(), &vec![&3, &2]);
+        assert_eq!(preferences.get(&1).unwrap(), &vec![&3, &2, &1]);
+
+        let preferences = Scoring::new(score)
+            .threshold(2.0)
+            .top_k(1)
+            .preferences(&agents, &candidates);
+        assert_eq!(preferences.get(&1).unwrap(), &vec![&3]);
+
+        let preferences = Scoring::new(score)
+            .threshold(10.0)
+            .preferences(&agents, &candidates);
+        assert!(preferences.get(&1).unwrap().is_empty());
+    }
+}
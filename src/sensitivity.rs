@@ -0,0 +1,319 @@
+//! How fragile the result of `gale_shapley` is to small mistakes in the preferences
+//!
+//! Every agent's list is perturbed by swapping two adjacent entries, one swap at a time, and the
+//! instance is solved again. Swaps that the original run never looked at are skipped, because they
+//! cannot change anything: entries after a man's partner (he never proposed to them), and two men
+//! who did not both propose to the woman (she never compared them).
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::{gale_shapley_internal, Interned, Side};
+
+/// A swap of two adjacent entries in an agent's list that changes the result of `gale_shapley`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriticalSwap<'a, T> {
+    /// The swap exchanges the agent's choices at `position` and `position + 1`
+    pub position: usize,
+    /// The choices at `position` and `position + 1` before the swap
+    pub swapped: (&'a T, &'a T),
+    /// The other men whose partner changes
+    pub affected_men: Vec<&'a T>,
+    /// The other women whose partner changes
+    pub affected_women: Vec<&'a T>,
+}
+
+impl<'a, T> CriticalSwap<'a, T> {
+    /// Number of agents, not counting the one whose list was perturbed, whose partner changes
+    pub fn affected(&self) -> usize {
+        return self.affected_men.len() + self.affected_women.len();
+    }
+}
+
+/// The critical swaps of every agent. See `analyze`
+#[derive(Debug, Clone)]
+pub struct Sensitivity<'a, T> {
+    /// Every man, with the swaps in his list that change the result. Robust men have none
+    pub men: HashMap<&'a T, Vec<CriticalSwap<'a, T>>>,
+    /// Every woman, with the swaps in her list that change the result
+    pub women: HashMap<&'a T, Vec<CriticalSwap<'a, T>>>,
+    /// Number of instances that had to be solved again
+    pub resolves: usize,
+}
+
+impl<'a, T> Sensitivity<'a, T> {
+    /// Total number of critical swaps over both sides
+    pub fn critical_swaps(&self) -> usize {
+        return self
+            .men
+            .values()
+            .chain(self.women.values())
+            .map(Vec::len)
+            .sum();
+    }
+}
+
+/**
+    Finds, for every agent, the swaps of two adjacent entries in their list that would change the
+    result of `gale_shapley`, and who else each swap would affect
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::sensitivity;
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let sensitivity = sensitivity::analyze(&men_preferences, &women_preferences);
+
+    // If cleopatra preferred julius, both men and boudica would get a new partner
+    let swaps = sensitivity.women.get(&"cleopatra").unwrap();
+    assert_eq!(swaps.len(), 1);
+    assert_eq!(swaps[0].swapped, (&"antony", &"julius"));
+    assert_eq!(swaps[0].affected(), 3);
+
+    // boudica never has to choose between the two men
+    assert!(sensitivity.women.get(&"boudica").unwrap().is_empty());
+    ```
+*/
+pub fn analyze<'a, T>(
+    input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+    input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+) -> Sensitivity<'a, T>
+where
+    T: Eq + Hash,
+{
+    let interned = Interned::new(input_men_preferences, input_women_preferences);
+
+    let mut proposers: HashMap<u32, HashSet<u32>> = HashMap::new();
+    let engaged_man_woman = gale_shapley_internal(
+        &interned.men_preferences,
+        &interned.women_preferences,
+        Some(&mut |outcome| {
+            for (woman, men) in &outcome.proposals {
+                proposers.entry(*woman).or_default().extend(men);
+            }
+        }),
+    );
+
+    let mut resolves = 0;
+    let mut men: HashMap<&T, Vec<CriticalSwap<T>>> = HashMap::new();
+    let mut women: HashMap<&T, Vec<CriticalSwap<T>>> = HashMap::new();
+
+    for (man, preferences) in &interned.men_preferences {
+        // He never proposed past his partner, so the order of the rest of his list does not matter
+        let proposed = match engaged_man_woman.get(man) {
+            Some(woman) => preferences.iter().position(|w| w == woman).unwrap() + 1,
+            None => preferences.len(),
+        };
+
+        let mut swaps: Vec<CriticalSwap<T>> = Vec::new();
+        for position in 0..proposed.min(preferences.len().saturating_sub(1)) {
+            let mut men_preferences = interned.men_preferences.clone();
+            men_preferences
+                .get_mut(man)
+                .unwrap()
+                .swap(position, position + 1);
+            let result = gale_shapley_internal(&men_preferences, &interned.women_preferences, None);
+            resolves += 1;
+
+            let swapped = (preferences[position], preferences[position + 1]);
+            if let Some(swap) = critical_swap(
+                &interned,
+                &engaged_man_woman,
+                &result,
+                position,
+                (interned.woman(swapped.0), interned.woman(swapped.1)),
+                (Side::Men, *man),
+            ) {
+                swaps.push(swap);
+            }
+        }
+        men.insert(interned.man(*man), swaps);
+    }
+
+    let nobody = HashSet::new();
+    for (woman, preferences) in &interned.women_preferences {
+        // She only ever compares men who proposed to her
+        let proposed = proposers.get(woman).unwrap_or(&nobody);
+
+        let mut swaps: Vec<CriticalSwap<T>> = Vec::new();
+        for position in 0..preferences.len().saturating_sub(1) {
+            let swapped = (preferences[position], preferences[position + 1]);
+            if !proposed.contains(&swapped.0) || !proposed.contains(&swapped.1) {
+                continue;
+            }
+
+            let mut women_preferences = interned.women_preferences.clone();
+            women_preferences
+                .get_mut(woman)
+                .unwrap()
+                .swap(position, position + 1);
+            let result = gale_shapley_internal(&interned.men_preferences, &women_preferences, None);
+            resolves += 1;
+
+            if let Some(swap) = critical_swap(
+                &interned,
+                &engaged_man_woman,
+                &result,
+                position,
+                (interned.man(swapped.0), interned.man(swapped.1)),
+                (Side::Women, *woman),
+            ) {
+                swaps.push(swap);
+            }
+        }
+        women.insert(interned.woman(*woman), swaps);
+    }
+
+    return Sensitivity {
+        men,
+        women,
+        resolves,
+    };
+}
+
+/// Compares the result after `swapper` swapped two choices with the original one. Returns `None` if nothing changed
+fn critical_swap<'a, T>(
+    interned: &Interned<'a, T>,
+    engaged_man_woman: &HashMap<u32, u32>,
+    result: &HashMap<u32, u32>,
+    position: usize,
+    swapped: (&'a T, &'a T),
+    swapper: (Side, u32),
+) -> Option<CriticalSwap<'a, T>>
+where
+    T: Eq + Hash,
+{
+    if result == engaged_man_woman {
+        return None;
+    }
+
+    let engaged_woman_man: HashMap<u32, u32> = engaged_man_woman
+        .iter()
+        .map(|(man, woman)| (*woman, *man))
+        .collect();
+    let result_woman_man: HashMap<u32, u32> =
+        result.iter().map(|(man, woman)| (*woman, *man)).collect();
+    let swapping_man = match swapper {
+        (Side::Men, man) => Some(man),
+        _ => None,
+    };
+    let swapping_woman = match swapper {
+        (Side::Women, woman) => Some(woman),
+        _ => None,
+    };
+
+    let mut affected_men: Vec<u32> = interned
+        .men_preferences
+        .keys()
+        .filter(|man| Some(**man) != swapping_man)
+        .filter(|man| engaged_man_woman.get(man) != result.get(man))
+        .cloned()
+        .collect();
+    let mut affected_women: Vec<u32> = interned
+        .women_preferences
+        .keys()
+        .filter(|woman| Some(**woman) != swapping_woman)
+        .filter(|woman| engaged_woman_man.get(woman) != result_woman_man.get(woman))
+        .cloned()
+        .collect();
+    affected_men.sort_unstable();
+    affected_women.sort_unstable();
+
+    return Some(CriticalSwap {
+        position,
+        swapped,
+        affected_men: affected_men.iter().map(|man| interned.man(*man)).collect(),
+        affected_women: affected_women
+            .iter()
+            .map(|woman| interned.woman(*woman))
+            .collect(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_first_choices_are_robust() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1, &2]);
+        men_preferences.insert(&1, vec![&1, &2, &0]);
+        men_preferences.insert(&2, vec![&2, &0, &1]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&1, &2, &0]);
+        women_preferences.insert(&1, vec![&2, &0, &1]);
+        women_preferences.insert(&2, vec![&0, &1, &2]);
+
+        // Everyone proposes once and is accepted. Only a man swapping his first choice away matters
+        let sensitivity = analyze(&men_preferences, &women_preferences);
+
+        assert_eq!(sensitivity.resolves, 3);
+        assert_eq!(sensitivity.critical_swaps(), 3);
+        for swaps in sensitivity.women.values() {
+            assert!(swaps.is_empty());
+        }
+
+        let swaps = sensitivity.men.get(&0).unwrap();
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(swaps[0].position, 0);
+        assert_eq!(swaps[0].swapped, (&0, &1));
+    }
+
+    #[test]
+    fn test_skipped_swaps_do_not_change_the_result() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(&0, vec![&0, &1, &2]);
+        men_preferences.insert(&1, vec![&0, &2, &1]);
+        men_preferences.insert(&2, vec![&0, &1, &2]);
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(&0, vec![&2, &1, &0]);
+        women_preferences.insert(&1, vec![&0, &1, &2]);
+        women_preferences.insert(&2, vec![&1, &2, &0]);
+
+        let original = crate::gale_shapley(&men_preferences, &women_preferences);
+        let sensitivity = analyze(&men_preferences, &women_preferences);
+
+        // Brute force every swap and compare with what the analysis reported
+        for (man, preferences) in &men_preferences {
+            for position in 0..preferences.len() - 1 {
+                let mut perturbed = men_preferences.clone();
+                perturbed.get_mut(man).unwrap().swap(position, position + 1);
+                let changed = crate::gale_shapley(&perturbed, &women_preferences) != original;
+                let reported = sensitivity
+                    .men
+                    .get(man)
+                    .unwrap()
+                    .iter()
+                    .any(|swap| swap.position == position);
+                assert_eq!(changed, reported);
+            }
+        }
+        for (woman, preferences) in &women_preferences {
+            for position in 0..preferences.len() - 1 {
+                let mut perturbed = women_preferences.clone();
+                perturbed
+                    .get_mut(woman)
+                    .unwrap()
+                    .swap(position, position + 1);
+                let changed = crate::gale_shapley(&men_preferences, &perturbed) != original;
+                let reported = sensitivity
+                    .women
+                    .get(woman)
+                    .unwrap()
+                    .iter()
+                    .any(|swap| swap.position == position);
+                assert_eq!(changed, reported);
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use crate::limits::{Interruption, Limits};
+use crate::{get_rank, OnRound, RoundOutcome};
+
+/// The McVitie-Wilson formulation of Gale-Shapley: men enter the market one at a time
+///
+/// In the original paper the displaced man proposes through a recursive call. That call is always
+/// the last thing that happens, so here it is a loop that swaps the proposer instead.
+pub(crate) fn mcvitie_wilson_internal(
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    on_round: OnRound,
+) -> HashMap<u32, u32> {
+    return continue_mcvitie_wilson_internal(
+        men_preferences,
+        women_preferences,
+        HashMap::new(),
+        on_round,
+    );
+}
+
+/// Brings in every man who is not in `engaged_man_woman` yet
+///
+/// `men_preferences` must only hold the women each man has not proposed to yet
+pub(crate) fn continue_mcvitie_wilson_internal(
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    engaged_man_woman: HashMap<u32, u32>,
+    mut on_round: OnRound,
+) -> HashMap<u32, u32> {
+    // index into each man's preferences of the next woman he will propose to
+    let mut next_choice: HashMap<u32, usize> = HashMap::new();
+    let mut engaged_woman_man: HashMap<u32, u32> = engaged_man_woman
+        .iter()
+        .map(|(man, woman)| (*woman, *man))
+        .collect();
+
+    let mut men: Vec<u32> = men_preferences
+        .keys()
+        .filter(|man| !engaged_man_woman.contains_key(man))
+        .cloned()
+        .collect();
+    men.sort_unstable();
+
+    // without a budget, a man's arrival never stops early
+    let mut proposals_left = usize::MAX;
+    for man in men {
+        let mut outcome = RoundOutcome::default();
+        introduce_man(
+            man,
+            men_preferences,
+            women_preferences,
+            &mut next_choice,
+            &mut engaged_woman_man,
+            if on_round.is_some() {
+                Some(&mut outcome)
+            } else {
+                None
+            },
+            &mut proposals_left,
+        );
+        if let Some(on_round) = &mut on_round {
+            on_round(&outcome);
+        }
+    }
+
+    return engaged_woman_man
+        .into_iter()
+        .map(|(woman, man)| (man, woman))
+        .collect();
+}
+
+/// Same as `mcvitie_wilson_internal`, but stops once one of `limits` is reached. Each man's arrival is a round
+///
+/// The proposal budget can stop an arrival halfway, right before the proposal that would go over it. Returns the
+/// engagements so far, the number of rounds and proposals, and why it stopped early if it did
+pub(crate) fn limited_mcvitie_wilson_internal(
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    limits: &Limits,
+) -> (HashMap<u32, u32>, usize, usize, Option<Interruption>) {
+    let mut next_choice: HashMap<u32, usize> = HashMap::new();
+    let mut engaged_woman_man: HashMap<u32, u32> = HashMap::new();
+    let mut rounds = 0;
+    let mut proposals = 0;
+    let mut interruption = None;
+
+    let mut men: Vec<u32> = men_preferences.keys().cloned().collect();
+    men.sort_unstable();
+
+    for man in men {
+        interruption = limits.check(rounds, proposals);
+        if interruption.is_some() {
+            break;
+        }
+
+        let budget = limits.proposals_left(proposals);
+        let mut proposals_left = budget;
+        let finished = introduce_man(
+            man,
+            men_preferences,
+            women_preferences,
+            &mut next_choice,
+            &mut engaged_woman_man,
+            None,
+            &mut proposals_left,
+        );
+        rounds += 1;
+        proposals += budget - proposals_left;
+        if !finished {
+            interruption = Some(Interruption::ProposalBudget);
+            break;
+        }
+    }
+
+    let engaged_man_woman = engaged_woman_man
+        .into_iter()
+        .map(|(woman, man)| (man, woman))
+        .collect();
+    return (engaged_man_woman, rounds, proposals, interruption);
+}
+
+/// Lets `man`, then whoever he displaces, propose until someone stays single or engaged, recording what happens
+/// in `outcome` if there is one. Returns false if it stops early because `proposals_left` ran out, leaving the
+/// last displaced man single
+fn introduce_man(
+    man: u32,
+    men_preferences: &HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    next_choice: &mut HashMap<u32, usize>,
+    engaged_woman_man: &mut HashMap<u32, u32>,
+    mut outcome: Option<&mut RoundOutcome>,
+    proposals_left: &mut usize,
+) -> bool {
+    let mut proposer = man;
+
+    loop {
+        let choice = next_choice.entry(proposer).or_insert(0);
+        let woman = match men_preferences.get(&proposer).unwrap().get(*choice) {
+            Some(woman) => *woman,
+            // He has been rejected by everyone on his list
+            None => return true,
+        };
+        if *proposals_left == 0 {
+            return false;
+        }
+        *proposals_left -= 1;
+        *choice += 1;
+
+        let accepted = match get_rank(women_preferences, &woman, &proposer) {
+            // He is not on her list
+            None => false,
+            Some(rank_proposer) => match engaged_woman_man.get(&woman) {
+                None => true,
+                Some(current_man) => {
+                    rank_proposer < get_rank(women_preferences, &woman, current_man).unwrap()
+                }
+            },
+        };
+        let displaced = if accepted {
+            engaged_woman_man.insert(woman, proposer)
+        } else {
+            None
+        };
+
+        if let Some(outcome) = outcome.as_deref_mut() {
+            outcome.proposals.entry(woman).or_default().insert(proposer);
+            if let Some(displaced) = displaced {
+                outcome.broken.push((displaced, woman));
+            }
+            if accepted {
+                outcome.accepted.push((proposer, woman));
+            } else {
+                outcome.rejected.push((proposer, woman));
+            }
+        }
+
+        match (accepted, displaced) {
+            (true, None) => return true,
+            (true, Some(displaced)) => proposer = displaced,
+            (false, _) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gale_shapley_internal;
+    use crate::tests::{get_preferences_config_1, get_preferences_config_2};
+
+    #[test]
+    fn test_same_result_as_rounds() {
+        let men_preferences = get_preferences_config_1();
+        let women_preferences = get_preferences_config_2();
+
+        let sequential = mcvitie_wilson_internal(&men_preferences, &women_preferences, None);
+        let rounds = gale_shapley_internal(&men_preferences, &women_preferences, None);
+
+        assert_eq!(sequential, rounds);
+    }
+
+    #[test]
+    fn test_displaced_man_keeps_proposing() {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(0, vec![0, 1]);
+        men_preferences.insert(1, vec![0, 1]);
+
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(0, vec![1, 0]);
+        women_preferences.insert(1, vec![1, 0]);
+
+        let mut outcomes = Vec::new();
+        let engaged_man_woman = mcvitie_wilson_internal(
+            &men_preferences,
+            &women_preferences,
+            Some(&mut |outcome| {
+                outcomes.push((
+                    outcome.proposals.len(),
+                    outcome.accepted.clone(),
+                    outcome.broken.clone(),
+                ))
+            }),
+        );
+
+        // man 0 walks in and gets woman 0. Then man 1 takes her away, and man 0 moves on to woman 1
+        assert_eq!(outcomes[0], (1, vec![(0, 0)], vec![]));
+        assert_eq!(outcomes[1], (2, vec![(1, 0), (0, 1)], vec![(0, 0)]));
+        assert_eq!(engaged_man_woman.get(&0), Some(&1));
+        assert_eq!(engaged_man_woman.get(&1), Some(&0));
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Instant;
+
+use crate::buffers::Buffers;
+use crate::limits::{Interrupted, Interruption, Limits};
+use crate::preferences::{solve_preferences, Preferences};
+use crate::sequential::{
+    continue_mcvitie_wilson_internal, limited_mcvitie_wilson_internal, mcvitie_wilson_internal,
+};
+use crate::warm_start::prepare_warm_start;
+use crate::{
+    continue_gale_shapley_internal, get_unengaged_men, play_round, Interned, Observer, OnRound,
+    Statistics,
+};
+
+/// The flavours of the Gale-Shapley algorithm that a `Solver` can run. All of them return the same, man-optimal, matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// All the un-engaged men propose at the same time, round after round. This is what `gale_shapley` does
+    #[default]
+    Rounds,
+    /// McVitie-Wilson: the men are brought in one at a time. A new man proposes until he is accepted, and the
+    /// man he displaces (if any) carries on proposing in his place. Each man's arrival is reported as one round
+    Sequential,
+}
+
+/**
+    Runs the Gale-Shapley algorithm with a given set of options
+
+    `gale_shapley`, `gale_shapley_with_observer` and `gale_shapley_with_statistics` are shorthands for
+    the default solver.
+
+    # Examples
+    ```
+    use std::collections::HashMap;
+    use matchertools::{Algorithm, Solver};
+
+    let mut men_preferences = HashMap::new();
+    let mut women_preferences = HashMap::new();
+
+    men_preferences.insert(&"julius", vec![&"cleopatra", &"boudica"]);
+    men_preferences.insert(&"antony", vec![&"cleopatra", &"boudica"]);
+
+    women_preferences.insert(&"cleopatra", vec![&"antony", &"julius"]);
+    women_preferences.insert(&"boudica", vec![&"julius", &"antony"]);
+
+    let solver = Solver::new().algorithm(Algorithm::Sequential);
+    let (engaged_man_woman, statistics) =
+        solver.solve_with_statistics(&men_preferences, &women_preferences);
+
+    assert_eq!(engaged_man_woman.get(&&"julius"), Some(&&"boudica"));
+    assert_eq!(engaged_man_woman.get(&&"antony"), Some(&&"cleopatra"));
+    assert_eq!(statistics.proposals, 3);
+    ```
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    algorithm: Algorithm,
+    threads: usize,
+    buffers: Buffers,
+}
+
+impl Solver {
+    /// A solver that runs `Algorithm::Rounds`
+    pub fn new() -> Solver {
+        return Solver::default();
+    }
+
+    /// Picks the algorithm to run
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Solver {
+        self.algorithm = algorithm;
+        return self;
+    }
+
+    /**
+        Splits the women across `threads` threads in every round of `Algorithm::Rounds`. Needs the `parallel` feature
+
+        Within a round, every woman decides about her proposals independently of the others, so the
+        decisions are worked out concurrently and applied in the same order as serially. The results,
+        including what observers and statistics see, are the same as with a single thread. Starting
+        threads every round only pays off on large instances; see `examples/parallel_rounds.rs`.
+
+        # Examples
+        ```
+        use matchertools::{generator, Solver};
+
+        let instance = generator::uniform(100, 100, 7);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        assert_eq!(
+            Solver::new().threads(4).solve(&men_preferences, &women_preferences),
+            matchertools::gale_shapley(&men_preferences, &women_preferences)
+        );
+        ```
+    */
+    #[cfg(feature = "parallel")]
+    pub fn threads(mut self, threads: usize) -> Solver {
+        self.threads = threads;
+        return self;
+    }
+
+    /// Allocates the memory that `solve_reusing` needs for instances of up to `men` men and `women` women
+    pub fn capacity(mut self, men: usize, women: usize) -> Solver {
+        self.buffers = Buffers::with_capacity(men, women);
+        return self;
+    }
+
+    /// Returns a stable matching of men to women. See `gale_shapley` for the expected input
+    pub fn solve<'a, T>(
+        &self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    ) -> HashMap<&'a T, &'a T>
+    where
+        T: Eq + Hash,
+    {
+        let interned = Interned::new(input_men_preferences, input_women_preferences);
+
+        // men_preferences and women_preferences is HashMap<u32, Vec<u32>>
+        let engagements_u32 = self.solve_internal(&interned, None);
+
+        return interned.engagements_from_u32(&engagements_u32);
+    }
+
+    /**
+        Same as `solve`, but keeps the solver's working memory around for the next call
+
+        The preferences are turned into flat arrays that stay allocated, as do the tables that look
+        agents up, so solving many instances of the same size only allocates the lists of men and women
+        and the returned matching. It always proposes one man at a time over those arrays: every algorithm gives the same matching, so
+        this one ignores the choice of algorithm and the number of threads.
+
+        # Examples
+        ```
+        use matchertools::{generator, Solver};
+
+        let mut solver = Solver::new().capacity(50, 50);
+        for seed in 0..10 {
+            let instance = generator::uniform(50, 50, seed);
+            let men_preferences = instance.men_preferences();
+            let women_preferences = instance.women_preferences();
+
+            assert_eq!(
+                solver.solve_reusing(&men_preferences, &women_preferences),
+                matchertools::gale_shapley(&men_preferences, &women_preferences)
+            );
+        }
+        ```
+    */
+    pub fn solve_reusing<'a, T>(
+        &mut self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    ) -> HashMap<&'a T, &'a T>
+    where
+        T: Eq + Hash,
+    {
+        return self
+            .buffers
+            .solve_instance(input_men_preferences, input_women_preferences);
+    }
+
+    /**
+        Same as `solve`, but stops early once one of `limits` is reached
+
+        Limits are checked before every round, and the error holds the tentative engagements at that
+        point. With `Algorithm::Rounds`, every round makes one proposal per free man, so a round that
+        would go over the proposal budget is never started; the rounds use the solver's `threads`. With
+        `Algorithm::Sequential`, a round is one man's arrival, and how many proposals it takes is only
+        known once it is over. The solve then stops in the middle of the arrival, right before the
+        proposal that would go over the budget, and counts that arrival as a round.
+
+        # Examples
+        ```
+        use matchertools::{generator, Interruption, Limits, Solver};
+
+        let instance = generator::uniform(20, 20, 3);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        // The first round alone makes 20 proposals
+        let limits = Limits::new().max_proposals(20);
+        let interrupted = Solver::new()
+            .try_solve(&men_preferences, &women_preferences, &limits)
+            .unwrap_err();
+
+        assert_eq!(interrupted.reason, Interruption::ProposalBudget);
+        assert_eq!((interrupted.rounds, interrupted.proposals), (1, 20));
+        assert!(interrupted.engagements.len() < 20);
+        ```
+    */
+    pub fn try_solve<'a, T>(
+        &self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+        limits: &Limits,
+    ) -> Result<HashMap<&'a T, &'a T>, Interrupted<'a, T>>
+    where
+        T: Eq + Hash,
+    {
+        let mut interned = Interned::new(input_men_preferences, input_women_preferences);
+
+        let (engaged_man_woman, rounds, proposals, interruption) = match self.algorithm {
+            Algorithm::Rounds => self.limited_rounds(
+                &mut interned.men_preferences,
+                &interned.women_preferences,
+                limits,
+            ),
+            Algorithm::Sequential => limited_mcvitie_wilson_internal(
+                &interned.men_preferences,
+                &interned.women_preferences,
+                limits,
+            ),
+        };
+
+        let engagements = interned.engagements_from_u32(&engaged_man_woman);
+        return match interruption {
+            None => Ok(engagements),
+            Some(reason) => Err(Interrupted {
+                reason,
+                engagements,
+                rounds,
+                proposals,
+            }),
+        };
+    }
+
+    /// Same as `solve`, but reports every event to `observer`. See `gale_shapley_with_observer`
+    pub fn solve_with_observer<'a, T, O>(
+        &self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+        observer: &mut O,
+    ) -> HashMap<&'a T, &'a T>
+    where
+        T: Eq + Hash,
+        O: Observer<T>,
+    {
+        let interned = Interned::new(input_men_preferences, input_women_preferences);
+
+        let engagements_u32 = self.solve_internal(
+            &interned,
+            Some(&mut |outcome| interned.notify(outcome, observer)),
+        );
+
+        return interned.engagements_from_u32(&engagements_u32);
+    }
+
+    /// Same as `solve`, but also returns statistics about the run. See `gale_shapley_with_statistics`
+    pub fn solve_with_statistics<'a, T>(
+        &self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+    ) -> (HashMap<&'a T, &'a T>, Statistics)
+    where
+        T: Eq + Hash,
+    {
+        let mut statistics = Statistics::default();
+
+        let started = Instant::now();
+        let interned = Interned::new(input_men_preferences, input_women_preferences);
+        statistics.interning_time = started.elapsed();
+
+        let started = Instant::now();
+        let engagements_u32 = self.solve_internal(
+            &interned,
+            Some(&mut |outcome| statistics.record_round(outcome)),
+        );
+        statistics.solving_time = started.elapsed();
+
+        let started = Instant::now();
+        let engagements = interned.engagements_from_u32(&engagements_u32);
+        statistics.mapping_time = started.elapsed();
+
+        return (engagements, statistics);
+    }
+
+    /// Same as `solve`, but carries on from a previous matching. See `gale_shapley_from`
+    pub fn solve_from<'a, T>(
+        &self,
+        input_men_preferences: &'a HashMap<&T, Vec<&T>>,
+        input_women_preferences: &'a HashMap<&T, Vec<&T>>,
+        previous_engagements: &HashMap<&T, &T>,
+    ) -> HashMap<&'a T, &'a T>
+    where
+        T: Eq + Hash,
+    {
+        let interned = Interned::new(input_men_preferences, input_women_preferences);
+
+        let mut men_preferences = interned.men_preferences.clone();
+        let engaged_man_woman = prepare_warm_start(
+            &mut men_preferences,
+            &interned.women_preferences,
+            &interned.engagements_to_u32(previous_engagements),
+        );
+
+        let engagements_u32 = match self.algorithm {
+            Algorithm::Rounds => continue_gale_shapley_internal(
+                men_preferences,
+                &interned.women_preferences,
+                engaged_man_woman,
+                self.threads,
+                None,
+            ),
+            Algorithm::Sequential => continue_mcvitie_wilson_internal(
+                &men_preferences,
+                &interned.women_preferences,
+                engaged_man_woman,
+                None,
+            ),
+        };
+
+        return interned.engagements_from_u32(&engagements_u32);
+    }
+
+    /// Same as `solve`, but for preferences given through the `Preferences` trait rather than as lists.
+    /// `men` are the proposers. See `Comparator` for an example
+    pub fn solve_with_preferences<A, I, M, W>(
+        &self,
+        men: I,
+        men_preferences: &M,
+        women_preferences: &W,
+    ) -> HashMap<A, A>
+    where
+        A: Copy + Eq + Hash,
+        I: IntoIterator<Item = A>,
+        M: Preferences<A> + ?Sized,
+        W: Preferences<A> + ?Sized,
+    {
+        return solve_preferences(
+            self.algorithm,
+            men.into_iter().collect(),
+            men_preferences,
+            women_preferences,
+        );
+    }
+
+    /// `try_solve` for `Algorithm::Rounds`. Returns the same as `limited_mcvitie_wilson_internal`
+    fn limited_rounds(
+        &self,
+        men_preferences: &mut HashMap<u32, Vec<u32>>,
+        women_preferences: &HashMap<u32, Vec<u32>>,
+        limits: &Limits,
+    ) -> (HashMap<u32, u32>, usize, usize, Option<Interruption>) {
+        let mut engaged_man_woman: HashMap<u32, u32> = HashMap::new();
+        let mut rounds = 0;
+        let mut proposals = 0;
+
+        loop {
+            let free_men = get_unengaged_men(men_preferences, &engaged_man_woman).len();
+            if free_men == 0 {
+                return (engaged_man_woman, rounds, proposals, None);
+            }
+            let interruption = limits.check(rounds, proposals + free_men);
+            if interruption.is_some() {
+                return (engaged_man_woman, rounds, proposals, interruption);
+            }
+
+            play_round(
+                men_preferences,
+                women_preferences,
+                &mut engaged_man_woman,
+                self.threads,
+                false,
+            );
+            rounds += 1;
+            proposals += free_men;
+        }
+    }
+
+    fn solve_internal<T>(&self, interned: &Interned<T>, on_round: OnRound) -> HashMap<u32, u32> {
+        return match self.algorithm {
+            Algorithm::Rounds => continue_gale_shapley_internal(
+                interned.men_preferences.clone(),
+                &interned.women_preferences,
+                HashMap::new(),
+                self.threads,
+                on_round,
+            ),
+            Algorithm::Sequential => mcvitie_wilson_internal(
+                &interned.men_preferences,
+                &interned.women_preferences,
+                on_round,
+            ),
+        };
+    }
+}
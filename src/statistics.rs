@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use crate::RoundOutcome;
+
+/// What it took to solve an instance. See `gale_shapley_with_statistics`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Statistics {
+    /// Number of rounds played
+    pub rounds: usize,
+    /// Total number of proposals made by the men
+    pub proposals: usize,
+    /// Number of proposals that were turned down
+    pub rejections: usize,
+    /// Number of tentative engagements that were broken off later on
+    pub broken_engagements: usize,
+    /// Time spent converting the input to the internal representation
+    pub interning_time: Duration,
+    /// Time spent running the algorithm itself
+    pub solving_time: Duration,
+    /// Time spent converting the result back to the input types
+    pub mapping_time: Duration,
+}
+
+impl Statistics {
+    /// Total wall-clock time of the solve
+    pub fn total_time(&self) -> Duration {
+        return self.interning_time + self.solving_time + self.mapping_time;
+    }
+
+    pub(crate) fn record_round(&mut self, outcome: &RoundOutcome) {
+        self.rounds += 1;
+        self.proposals += outcome
+            .proposals
+            .values()
+            .map(|men| men.len())
+            .sum::<usize>();
+        self.rejections += outcome.rejected.len();
+        self.broken_engagements += outcome.broken.len();
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::get_rank;
+
+/**
+    Prepares a previous matching so that deferred acceptance can carry on from it
+
+    Pairs that are no longer acceptable to both sides are dropped. Every man who keeps his partner is
+    treated as if he had already proposed to (and been rejected by) every woman he prefers to her, so
+    his preferences are trimmed to the women after his partner. That is only true if none of those
+    women would rather have him than whoever she is with now. Men for whom it is not true are
+    unmatched again and start over from the top of their list. Unmatching them frees their partners,
+    which can make other men's assumptions false, so this repeats until nothing changes.
+
+    Deferred acceptance from the returned engagements only ever makes women better off, so every man
+    who is rejected in the end really was rejected by a woman who holds someone better. The final
+    matching is therefore stable (Blum, Roth and Rothblum, 1997). It is not necessarily the
+    man-optimal one: it stays as close to the previous matching as deferred acceptance allows.
+
+    Returns the engagements to start from.
+*/
+pub(crate) fn prepare_warm_start(
+    men_preferences: &mut HashMap<u32, Vec<u32>>,
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    previous_engagements: &HashMap<u32, u32>,
+) -> HashMap<u32, u32> {
+    let mut engaged_man_woman: HashMap<u32, u32> = HashMap::new();
+    for (man, woman) in previous_engagements {
+        let acceptable_to_man = get_rank(men_preferences, man, woman).is_some();
+        let acceptable_to_woman = get_rank(women_preferences, woman, man).is_some();
+        if acceptable_to_man && acceptable_to_woman {
+            engaged_man_woman.insert(*man, *woman);
+        }
+    }
+
+    loop {
+        let engaged_woman_man: HashMap<u32, u32> = engaged_man_woman
+            .iter()
+            .map(|(man, woman)| (*woman, *man))
+            .collect();
+
+        let mut restarting_men: Vec<u32> = Vec::new();
+        for (man, woman) in &engaged_man_woman {
+            let preferences = men_preferences.get(man).unwrap();
+            for better_woman in preferences.iter().take_while(|choice| *choice != woman) {
+                if would_accept(women_preferences, &engaged_woman_man, *better_woman, *man) {
+                    restarting_men.push(*man);
+                    break;
+                }
+            }
+        }
+
+        if restarting_men.is_empty() {
+            break;
+        }
+        for man in restarting_men {
+            engaged_man_woman.remove(&man);
+        }
+    }
+
+    for (man, woman) in &engaged_man_woman {
+        let preferences = men_preferences.get_mut(man).unwrap();
+        let position = preferences
+            .iter()
+            .position(|choice| choice == woman)
+            .unwrap();
+        preferences.drain(..=position);
+    }
+
+    return engaged_man_woman;
+}
+
+/// Returns true if `woman` prefers `man` to her current partner, or is single and finds him acceptable
+pub(crate) fn would_accept(
+    women_preferences: &HashMap<u32, Vec<u32>>,
+    engaged_woman_man: &HashMap<u32, u32>,
+    woman: u32,
+    man: u32,
+) -> bool {
+    let rank_man = match get_rank(women_preferences, &woman, &man) {
+        Some(rank) => rank,
+        None => return false,
+    };
+
+    return match engaged_woman_man.get(&woman) {
+        Some(current_man) => rank_man < get_rank(women_preferences, &woman, current_man).unwrap(),
+        None => true,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_preferences() -> (HashMap<u32, Vec<u32>>, HashMap<u32, Vec<u32>>) {
+        let mut men_preferences = HashMap::new();
+        men_preferences.insert(0, vec![0, 1, 2]);
+        men_preferences.insert(1, vec![1, 0, 2]);
+        men_preferences.insert(2, vec![0, 1, 2]);
+
+        let mut women_preferences = HashMap::new();
+        women_preferences.insert(0, vec![1, 0, 2]);
+        women_preferences.insert(1, vec![0, 1, 2]);
+        women_preferences.insert(2, vec![0, 1, 2]);
+
+        return (men_preferences, women_preferences);
+    }
+
+    #[test]
+    fn test_stable_previous_matching_is_kept() {
+        let (mut men_preferences, women_preferences) = get_preferences();
+        let mut previous = HashMap::new();
+        previous.insert(0, 0);
+        previous.insert(1, 1);
+        previous.insert(2, 2);
+
+        let engaged_man_woman =
+            prepare_warm_start(&mut men_preferences, &women_preferences, &previous);
+
+        assert_eq!(engaged_man_woman, previous);
+        assert_eq!(men_preferences.get(&0), Some(&vec![1, 2]));
+        assert_eq!(men_preferences.get(&2), Some(&vec![]));
+    }
+
+    #[test]
+    fn test_unacceptable_pairs_are_dropped() {
+        let (mut men_preferences, mut women_preferences) = get_preferences();
+        women_preferences.insert(2, vec![0, 1]);
+        let mut previous = HashMap::new();
+        previous.insert(0, 0);
+        previous.insert(1, 1);
+        previous.insert(2, 2);
+
+        let engaged_man_woman =
+            prepare_warm_start(&mut men_preferences, &women_preferences, &previous);
+
+        assert_eq!(engaged_man_woman.get(&2), None);
+        assert_eq!(men_preferences.get(&2), Some(&vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn test_restart_cascades() {
+        let (mut men_preferences, women_preferences) = get_preferences();
+        // Woman 1 would rather have man 0 or man 1 than man 2, so both of them start over. That
+        // frees woman 0, who would take man 2 over nobody, so he starts over too
+        let mut previous = HashMap::new();
+        previous.insert(0, 2);
+        previous.insert(1, 0);
+        previous.insert(2, 1);
+
+        let engaged_man_woman =
+            prepare_warm_start(&mut men_preferences, &women_preferences, &previous);
+
+        assert!(engaged_man_woman.is_empty());
+        assert_eq!(men_preferences.get(&0), Some(&vec![0, 1, 2]));
+        assert_eq!(men_preferences.get(&2), Some(&vec![0, 1, 2]));
+    }
+}
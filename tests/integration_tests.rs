@@ -1,7 +1,11 @@
-use matchertools;
+// The crate writes explicit returns throughout
+#![allow(clippy::needless_return)]
+
 use std::collections::HashMap;
 use std::str::FromStr;
 
+use matchertools::generator;
+
 #[test]
 fn test_simple_case() {
     let mut men_preferences = HashMap::new();
@@ -62,8 +66,10 @@ fn test_simple_case_string_2() {
     assert_eq!(engaged_man_woman.get(&&"vercingetorix"), Some(&&"boudica"));
 }
 
-#[test]
-fn test_moderate_case() {
+type Preferences = HashMap<&'static u32, Vec<&'static u32>>;
+
+/// Five men and five women, each with a full list
+fn moderate_case() -> (Preferences, Preferences) {
     let mut men_preferences = HashMap::new();
     men_preferences.insert(&0, vec![&0, &1, &2, &3, &4]);
     men_preferences.insert(&1, vec![&4, &3, &2, &1, &0]);
@@ -78,6 +84,14 @@ fn test_moderate_case() {
     women_preferences.insert(&3, vec![&0, &4, &3, &1, &2]);
     women_preferences.insert(&4, vec![&3, &0, &2, &4, &1]);
 
+    return (men_preferences, women_preferences);
+}
+
+#[test]
+#[allow(clippy::unnecessary_mut_passed)] // the test predates gale_shapley taking shared references
+fn test_moderate_case() {
+    let (mut men_preferences, mut women_preferences) = moderate_case();
+
     let engaged_man_woman =
         matchertools::gale_shapley(&mut men_preferences, &mut women_preferences);
 
@@ -87,3 +101,498 @@ fn test_moderate_case() {
     assert_eq!(engaged_man_woman.get(&&3), Some(&&2));
     assert_eq!(engaged_man_woman.get(&&4), Some(&&4));
 }
+
+#[test]
+fn test_run_rounds_moderate_case() {
+    let (men_preferences, women_preferences) = moderate_case();
+
+    let mut run = matchertools::GaleShapleyRun::new(&men_preferences, &women_preferences);
+    assert!(!run.is_finished());
+
+    let first_round = run.next().unwrap();
+    assert_eq!(first_round.proposals.len(), 5);
+    assert_eq!(first_round.accepted.len(), 3);
+    assert_eq!(first_round.rejected.len(), 2);
+    assert!(first_round.rejected.contains(&(&1, &4)));
+    assert!(first_round.rejected.contains(&(&2, &0)));
+    assert_eq!(first_round.engagements.get(&&3), Some(&&2));
+
+    let rounds: Vec<_> = run.by_ref().collect();
+    assert_eq!(rounds.len(), 1);
+    assert_eq!(rounds[0].number, 2);
+    assert_eq!(run.rounds_played(), 2);
+    assert!(run.is_finished());
+
+    assert_eq!(
+        run.engagements(),
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+}
+
+#[derive(Default)]
+struct RecordingObserver {
+    proposals: Vec<(u32, u32)>,
+    accepted: Vec<(u32, u32)>,
+    rejected: Vec<(u32, u32)>,
+    broken: Vec<(u32, u32)>,
+}
+
+impl matchertools::Observer<u32> for RecordingObserver {
+    fn on_proposal(&mut self, man: &u32, woman: &u32) {
+        self.proposals.push((*man, *woman));
+    }
+
+    fn on_accept(&mut self, man: &u32, woman: &u32) {
+        self.accepted.push((*man, *woman));
+    }
+
+    fn on_reject(&mut self, man: &u32, woman: &u32) {
+        self.rejected.push((*man, *woman));
+    }
+
+    fn on_break_engagement(&mut self, man: &u32, woman: &u32) {
+        self.broken.push((*man, *woman));
+    }
+}
+
+#[test]
+fn test_observer_sees_broken_engagement() {
+    let mut men_preferences = HashMap::new();
+    men_preferences.insert(&0, vec![&0, &1, &2]);
+    men_preferences.insert(&1, vec![&0, &1, &2]);
+    men_preferences.insert(&2, vec![&1, &2, &0]);
+
+    let mut women_preferences = HashMap::new();
+    women_preferences.insert(&0, vec![&0, &1, &2]);
+    women_preferences.insert(&1, vec![&1, &2, &0]);
+    women_preferences.insert(&2, vec![&0, &1, &2]);
+
+    let mut observer = RecordingObserver::default();
+    let engaged_man_woman = matchertools::gale_shapley_with_observer(
+        &men_preferences,
+        &women_preferences,
+        &mut observer,
+    );
+
+    assert_eq!(
+        engaged_man_woman,
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+    assert_eq!(engaged_man_woman.get(&&2), Some(&&2));
+
+    assert_eq!(observer.proposals.len(), 5);
+    assert_eq!(observer.broken, vec![(2, 1)]);
+    assert_eq!(observer.rejected, vec![(1, 0)]);
+    assert_eq!(observer.accepted.len(), 4);
+    assert_eq!(observer.accepted.last(), Some(&(2, 2)));
+}
+
+#[test]
+fn test_statistics_moderate_case() {
+    let (men_preferences, women_preferences) = moderate_case();
+
+    let (engaged_man_woman, statistics) =
+        matchertools::gale_shapley_with_statistics(&men_preferences, &women_preferences);
+
+    assert_eq!(
+        engaged_man_woman,
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+    assert_eq!(statistics.rounds, 2);
+    assert_eq!(statistics.proposals, 7);
+    assert_eq!(statistics.rejections, 2);
+    assert_eq!(statistics.broken_engagements, 0);
+    assert!(statistics.total_time() >= statistics.solving_time);
+}
+
+#[test]
+fn test_sequential_solver_moderate_case() {
+    let (men_preferences, women_preferences) = moderate_case();
+
+    let solver = matchertools::Solver::new().algorithm(matchertools::Algorithm::Sequential);
+    let (engaged_man_woman, statistics) =
+        solver.solve_with_statistics(&men_preferences, &women_preferences);
+
+    assert_eq!(
+        engaged_man_woman,
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+    assert_eq!(statistics.rounds, 5);
+    // Every man proposes to the same women in both algorithms, only the order differs
+    assert_eq!(statistics.proposals, 7);
+    assert_eq!(statistics.rejections + statistics.broken_engagements, 2);
+}
+
+#[test]
+fn test_metrics_moderate_case() {
+    let (men_preferences, women_preferences) = moderate_case();
+
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+    let metrics =
+        matchertools::metrics::compute(&men_preferences, &women_preferences, &engaged_man_woman);
+
+    // 0-0, 1-3, 2-1, 3-2, 4-4
+    assert_eq!(metrics.men.rank_histogram, vec![3, 2]);
+    assert_eq!(metrics.men.total_rank, 2);
+    assert_eq!(metrics.women.rank_histogram, vec![1, 1, 0, 2, 1]);
+    assert_eq!(metrics.women.total_rank, 11);
+    assert_eq!(metrics.egalitarian_cost, 13);
+    assert_eq!(metrics.sex_equality_cost, 9);
+    assert_eq!(metrics.regret, 4);
+    assert_eq!(metrics.men.unmatched + metrics.women.unmatched, 0);
+}
+
+fn is_stable(
+    men_preferences: &HashMap<&u32, Vec<&u32>>,
+    women_preferences: &HashMap<&u32, Vec<&u32>>,
+    engaged_man_woman: &HashMap<&u32, &u32>,
+) -> bool {
+    let engaged_woman_man: HashMap<&u32, &u32> = engaged_man_woman
+        .iter()
+        .map(|(man, woman)| (*woman, *man))
+        .collect();
+    let rank = |preferences: &Vec<&u32>, agent: &u32| preferences.iter().position(|a| *a == agent);
+
+    for (man, man_preferences) in men_preferences {
+        for woman in man_preferences {
+            if engaged_man_woman.get(man) == Some(woman) {
+                break;
+            }
+            let woman_preferences = women_preferences.get(woman).unwrap();
+            let rank_man = match rank(woman_preferences, man) {
+                Some(rank_man) => rank_man,
+                None => continue,
+            };
+            let blocking = match engaged_woman_man.get(woman) {
+                Some(current_man) => rank_man < rank(woman_preferences, current_man).unwrap(),
+                None => true,
+            };
+            if blocking {
+                return false;
+            }
+        }
+    }
+
+    return true;
+}
+
+#[test]
+fn test_incomplete_lists() {
+    let mut men_preferences = HashMap::new();
+    men_preferences.insert(&0, vec![&0]);
+    men_preferences.insert(&1, vec![&0, &1]);
+    men_preferences.insert(&2, vec![&1]);
+
+    let mut women_preferences = HashMap::new();
+    women_preferences.insert(&0, vec![&1, &0]);
+    women_preferences.insert(&1, vec![&2]);
+
+    for algorithm in [
+        matchertools::Algorithm::Rounds,
+        matchertools::Algorithm::Sequential,
+    ] {
+        let solver = matchertools::Solver::new().algorithm(algorithm);
+        let engaged_man_woman = solver.solve(&men_preferences, &women_preferences);
+
+        assert_eq!(engaged_man_woman.len(), 2);
+        assert_eq!(engaged_man_woman.get(&&0), None);
+        assert_eq!(engaged_man_woman.get(&&1), Some(&&0));
+        assert_eq!(engaged_man_woman.get(&&2), Some(&&1));
+    }
+}
+
+#[test]
+fn test_warm_start_keeps_stable_matching() {
+    let (men_preferences, women_preferences) = moderate_case();
+
+    // The woman-optimal matching is stable too, so a warm start should leave it alone
+    let woman_optimal: HashMap<&u32, &u32> =
+        matchertools::gale_shapley(&women_preferences, &men_preferences)
+            .into_iter()
+            .map(|(woman, man)| (man, woman))
+            .collect();
+
+    let engaged_man_woman =
+        matchertools::gale_shapley_from(&men_preferences, &women_preferences, &woman_optimal);
+    assert_eq!(engaged_man_woman, woman_optimal);
+
+    // Man 3 drops woman 2 from his list. Everything else should be re-stabilized around that
+    let mut new_men_preferences = men_preferences.clone();
+    new_men_preferences.insert(&3, vec![&4, &3, &0, &1]);
+
+    for algorithm in [
+        matchertools::Algorithm::Rounds,
+        matchertools::Algorithm::Sequential,
+    ] {
+        let solver = matchertools::Solver::new().algorithm(algorithm);
+        let engaged_man_woman =
+            solver.solve_from(&new_men_preferences, &women_preferences, &woman_optimal);
+
+        assert!(is_stable(
+            &new_men_preferences,
+            &women_preferences,
+            &engaged_man_woman
+        ));
+        assert_eq!(engaged_man_woman.len(), 5);
+    }
+}
+
+#[test]
+fn test_market_stays_stable() {
+    let (mut men_preferences, mut women_preferences) = moderate_case();
+
+    let mut market = matchertools::Market::from_preferences(&men_preferences, &women_preferences);
+    assert_eq!(
+        market.engagements(),
+        matchertools::gale_shapley(&men_preferences, &women_preferences)
+    );
+
+    // Woman 0 drops man 0, who is her first choice
+    market.insert_woman(0, vec![1, 2, 3, 4]);
+    women_preferences.insert(&0, vec![&1, &2, &3, &4]);
+    assert!(is_stable(
+        &men_preferences,
+        &women_preferences,
+        &market.engagements()
+    ));
+
+    // Man 4 leaves
+    let update = market.remove_man(&4);
+    men_preferences.remove(&4);
+    assert!(is_stable(
+        &men_preferences,
+        &women_preferences,
+        &market.engagements()
+    ));
+    assert_eq!(update.women.get(&4).unwrap().before, Some(4));
+
+    // Man 5 joins and is everybody's favourite
+    let five = 5;
+    market.insert_man(5, vec![3, 2, 1, 0, 4]);
+    men_preferences.insert(&five, vec![&3, &2, &1, &0, &4]);
+    for woman in [0, 1, 2, 3, 4] {
+        let mut preferences = vec![5];
+        preferences.extend(
+            women_preferences
+                .get(&woman)
+                .unwrap()
+                .iter()
+                .map(|man| **man),
+        );
+        market.insert_woman(woman, preferences);
+    }
+    let women_preferences: HashMap<&u32, Vec<&u32>> = women_preferences
+        .iter()
+        .map(|(woman, men)| {
+            let mut preferences = vec![&five];
+            preferences.extend(men.iter().cloned());
+            return (*woman, preferences);
+        })
+        .collect();
+
+    assert_eq!(market.partner_of_man(&5), Some(&3));
+    assert!(is_stable(
+        &men_preferences,
+        &women_preferences,
+        &market.engagements()
+    ));
+    assert_eq!(market.engagements().len(), 5);
+}
+
+fn permutations(items: &[u32]) -> Vec<Vec<u32>> {
+    if items.is_empty() {
+        return vec![vec![]];
+    }
+
+    let mut result = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(i);
+        for mut permutation in permutations(&rest) {
+            permutation.insert(0, *item);
+            result.push(permutation);
+        }
+    }
+
+    return result;
+}
+
+#[test]
+fn test_closest_stable_matching_against_every_reference() {
+    let agents: Vec<u32> = (0..5).collect();
+    let mut men_preferences = HashMap::new();
+    men_preferences.insert(
+        &agents[0],
+        vec![&agents[0], &agents[1], &agents[2], &agents[3], &agents[4]],
+    );
+    men_preferences.insert(
+        &agents[1],
+        vec![&agents[1], &agents[2], &agents[3], &agents[4], &agents[0]],
+    );
+    men_preferences.insert(
+        &agents[2],
+        vec![&agents[2], &agents[3], &agents[4], &agents[0], &agents[1]],
+    );
+    men_preferences.insert(
+        &agents[3],
+        vec![&agents[3], &agents[4], &agents[0], &agents[1], &agents[2]],
+    );
+    men_preferences.insert(
+        &agents[4],
+        vec![&agents[4], &agents[0], &agents[1], &agents[2], &agents[3]],
+    );
+
+    let mut women_preferences = HashMap::new();
+    women_preferences.insert(
+        &agents[0],
+        vec![&agents[1], &agents[2], &agents[3], &agents[4], &agents[0]],
+    );
+    women_preferences.insert(
+        &agents[1],
+        vec![&agents[2], &agents[3], &agents[4], &agents[0], &agents[1]],
+    );
+    women_preferences.insert(
+        &agents[2],
+        vec![&agents[3], &agents[4], &agents[0], &agents[1], &agents[2]],
+    );
+    women_preferences.insert(
+        &agents[3],
+        vec![&agents[4], &agents[0], &agents[1], &agents[2], &agents[3]],
+    );
+    women_preferences.insert(
+        &agents[4],
+        vec![&agents[0], &agents[1], &agents[2], &agents[3], &agents[4]],
+    );
+
+    let all_matchings: Vec<HashMap<&u32, &u32>> = permutations(&agents)
+        .into_iter()
+        .map(|permutation| {
+            agents
+                .iter()
+                .zip(permutation)
+                .map(|(man, woman)| (man, &agents[woman as usize]))
+                .collect()
+        })
+        .collect();
+    let stable_matchings: Vec<&HashMap<&u32, &u32>> = all_matchings
+        .iter()
+        .filter(|matching| is_stable(&men_preferences, &women_preferences, matching))
+        .collect();
+    assert_eq!(stable_matchings.len(), 5);
+
+    let common_pairs = |a: &HashMap<&u32, &u32>, b: &HashMap<&u32, &u32>| {
+        a.iter()
+            .filter(|(man, woman)| b.get(*man) == Some(*woman))
+            .count()
+    };
+
+    for reference in &all_matchings {
+        let closest =
+            matchertools::closest_stable_matching(&men_preferences, &women_preferences, reference);
+        assert!(is_stable(&men_preferences, &women_preferences, &closest));
+
+        let best = stable_matchings
+            .iter()
+            .map(|matching| common_pairs(matching, reference))
+            .max()
+            .unwrap();
+        assert_eq!(common_pairs(&closest, reference), best);
+    }
+}
+
+#[test]
+fn test_generated_instances_are_solved_stably() {
+    let instances = vec![
+        generator::uniform(12, 10, 1),
+        generator::incomplete(12, 12, 0.4, 2),
+        generator::master_list(10, 12, 0.2, 3),
+        generator::mallows(10, 10, 0.6, 4),
+        generator::euclidean(10, 10, 5),
+    ];
+
+    for instance in instances {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+        assert!(is_stable(
+            &men_preferences,
+            &women_preferences,
+            &engaged_man_woman
+        ));
+    }
+}
+
+#[test]
+fn test_resumed_run_finishes_like_an_uninterrupted_one() {
+    let instance = generator::uniform(12, 12, 9);
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+
+    let uninterrupted: Vec<usize> =
+        matchertools::GaleShapleyRun::new(&men_preferences, &women_preferences)
+            .map(|round| round.proposals.len())
+            .collect();
+    assert!(uninterrupted.len() > 2);
+
+    for stop_after in 0..uninterrupted.len() {
+        let mut run = matchertools::GaleShapleyRun::new(&men_preferences, &women_preferences);
+        run.by_ref().take(stop_after).for_each(drop);
+
+        let mut file = Vec::new();
+        run.checkpoint().write(&mut file).unwrap();
+        let checkpoint = matchertools::Checkpoint::read(file.as_slice()).unwrap();
+
+        let mut resumed =
+            matchertools::GaleShapleyRun::resume(&men_preferences, &women_preferences, &checkpoint)
+                .unwrap();
+        let rest: Vec<usize> = resumed
+            .by_ref()
+            .map(|round| round.proposals.len())
+            .collect();
+
+        assert_eq!(rest, uninterrupted[stop_after..].to_vec());
+        assert_eq!(resumed.rounds_played(), uninterrupted.len());
+        assert_eq!(
+            resumed.engagements(),
+            matchertools::gale_shapley(&men_preferences, &women_preferences)
+        );
+    }
+}
+
+#[test]
+fn test_csv_instances_are_solved_like_generated_ones() {
+    use matchertools::csv;
+    use std::collections::HashMap;
+
+    let to_csv = |preferences: &HashMap<u32, Vec<u32>>| -> String {
+        let mut rows = String::new();
+        for (agent, choices) in preferences {
+            let choices: Vec<String> = choices.iter().map(|choice| choice.to_string()).collect();
+            rows.push_str(&format!("{}, {},,\n", agent, choices.join(", ")));
+        }
+        return rows;
+    };
+
+    for seed in 0..10 {
+        let instance = generator::incomplete(7, 6, 0.7, seed);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let expected = matchertools::gale_shapley(&men_preferences, &women_preferences);
+
+        let read = csv::read_instance(
+            to_csv(&instance.men).as_bytes(),
+            to_csv(&instance.women).as_bytes(),
+        )
+        .unwrap();
+        let read_men_preferences = read.men_preferences();
+        let read_women_preferences = read.women_preferences();
+        let engaged_man_woman =
+            matchertools::gale_shapley(&read_men_preferences, &read_women_preferences);
+
+        let mut expected_csv = Vec::new();
+        csv::write_matching(&mut expected_csv, &expected).unwrap();
+        let mut written_csv = Vec::new();
+        csv::write_matching(&mut written_csv, &engaged_man_woman).unwrap();
+        assert_eq!(written_csv, expected_csv);
+    }
+}
@@ -0,0 +1,295 @@
+//! Randomized checks of the solvers against `brute_force`, on small generated instances
+
+// The crate writes explicit returns throughout
+#![allow(clippy::needless_return)]
+
+use std::collections::HashMap;
+
+use matchertools::{brute_force, generator, Algorithm, Instance, MasterList, Solver, Streams};
+
+/// A mix of every generator, with sizes small enough for `brute_force`
+fn instances() -> Vec<Instance<u32>> {
+    let mut instances = Vec::new();
+    for seed in 0..40 {
+        let n = 1 + (seed % 6) as usize;
+        instances.push(generator::uniform(n, n, seed));
+        instances.push(generator::uniform(n, 6 - n + 1, seed));
+        instances.push(generator::incomplete(n, n, 0.5, seed));
+        instances.push(generator::master_list(n, n, 0.3, seed));
+        instances.push(generator::mallows(n, n, 0.8, seed));
+        instances.push(generator::euclidean(n, n, seed));
+    }
+
+    return instances;
+}
+
+/// Instances where the women leave out some of the men who list them, so acceptability is one-sided
+fn one_sided_instances() -> Vec<Instance<u32>> {
+    return (0..40)
+        .map(|seed| {
+            let n = 1 + (seed % 6) as usize;
+            let mut instance = generator::uniform(n, n, seed);
+            for (woman, men) in instance.women.iter_mut() {
+                men.retain(|man| (man + woman + seed as u32) % 3 != 0);
+            }
+            return instance;
+        })
+        .collect();
+}
+
+fn swap_sides<'a>(engaged: HashMap<&'a u32, &'a u32>) -> HashMap<&'a u32, &'a u32> {
+    return engaged
+        .into_iter()
+        .map(|(woman, man)| (man, woman))
+        .collect();
+}
+
+#[test]
+fn test_gale_shapley_is_man_optimal() {
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        assert_eq!(
+            matchertools::gale_shapley(&men_preferences, &women_preferences),
+            brute_force::man_optimal(&men_preferences, &women_preferences),
+            "{:?}",
+            instance
+        );
+    }
+}
+
+#[test]
+fn test_sequential_solver_is_man_optimal() {
+    let solver = Solver::new().algorithm(Algorithm::Sequential);
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        assert_eq!(
+            solver.solve(&men_preferences, &women_preferences),
+            brute_force::man_optimal(&men_preferences, &women_preferences),
+            "{:?}",
+            instance
+        );
+    }
+}
+
+#[test]
+fn test_women_proposing_is_woman_optimal() {
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        assert_eq!(
+            swap_sides(matchertools::gale_shapley(
+                &women_preferences,
+                &men_preferences
+            )),
+            brute_force::woman_optimal(&men_preferences, &women_preferences),
+            "{:?}",
+            instance
+        );
+    }
+}
+
+#[test]
+fn test_every_stable_matching_matches_the_same_agents() {
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let stable_matchings = brute_force::stable_matchings(&men_preferences, &women_preferences);
+
+        let mut matched_men: Vec<Vec<&u32>> = stable_matchings
+            .iter()
+            .map(|matching| {
+                let mut men: Vec<&u32> = matching.keys().cloned().collect();
+                men.sort();
+                return men;
+            })
+            .collect();
+        matched_men.dedup();
+        assert_eq!(matched_men.len(), 1, "{:?}", instance);
+    }
+}
+
+/// Number of pairs that are in one matching but not in the other
+fn differing_pairs(a: &HashMap<&u32, &u32>, b: &HashMap<&u32, &u32>) -> usize {
+    let missing_from = |a: &HashMap<&u32, &u32>, b: &HashMap<&u32, &u32>| {
+        a.iter()
+            .filter(|(man, woman)| b.get(*man) != Some(*woman))
+            .count()
+    };
+    return missing_from(a, b) + missing_from(b, a);
+}
+
+#[test]
+fn test_warm_start_and_closest_matching_are_stable() {
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let stable_matchings = brute_force::stable_matchings(&men_preferences, &women_preferences);
+
+        // An arbitrary, usually unstable, matching: man i with woman i
+        let identity: HashMap<&u32, &u32> = men_preferences
+            .keys()
+            .filter_map(|man| {
+                women_preferences
+                    .get_key_value(*man)
+                    .map(|(woman, _)| (*man, *woman))
+            })
+            .collect();
+        // Men with an even id take their man-optimal partner, the others their woman-optimal one
+        let man_optimal = brute_force::man_optimal(&men_preferences, &women_preferences);
+        let woman_optimal = brute_force::woman_optimal(&men_preferences, &women_preferences);
+        let mixed: HashMap<&u32, &u32> = men_preferences
+            .keys()
+            .filter_map(|man| {
+                let optimal = if **man % 2 == 0 {
+                    &man_optimal
+                } else {
+                    &woman_optimal
+                };
+                return optimal.get(man).map(|woman| (*man, *woman));
+            })
+            .collect();
+
+        let mut references = vec![identity, mixed];
+        references.extend(stable_matchings.iter().cloned());
+        for reference in &references {
+            let warm_started =
+                matchertools::gale_shapley_from(&men_preferences, &women_preferences, reference);
+            assert!(stable_matchings.contains(&warm_started), "{:?}", instance);
+
+            let closest = matchertools::closest_stable_matching(
+                &men_preferences,
+                &women_preferences,
+                reference,
+            );
+            assert!(stable_matchings.contains(&closest), "{:?}", instance);
+
+            let fewest_differences = stable_matchings
+                .iter()
+                .map(|matching| differing_pairs(matching, reference))
+                .min()
+                .unwrap();
+            assert_eq!(
+                differing_pairs(&closest, reference),
+                fewest_differences,
+                "{:?} {:?}",
+                instance,
+                reference
+            );
+        }
+    }
+}
+
+#[test]
+fn test_trait_preferences_match_lists() {
+    for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+        let solver = Solver::new().algorithm(*algorithm);
+        for instance in instances() {
+            let men_preferences = instance.men_preferences();
+            let women_preferences = instance.women_preferences();
+
+            assert_eq!(
+                solver.solve_with_preferences(
+                    men_preferences.keys().cloned(),
+                    &men_preferences,
+                    &women_preferences
+                ),
+                matchertools::gale_shapley(&men_preferences, &women_preferences),
+                "{:?}",
+                instance
+            );
+        }
+    }
+}
+
+#[test]
+fn test_one_sided_unacceptability() {
+    for algorithm in [Algorithm::Rounds, Algorithm::Sequential].iter() {
+        let solver = Solver::new().algorithm(*algorithm);
+        for instance in one_sided_instances() {
+            let men_preferences = instance.men_preferences();
+            let women_preferences = instance.women_preferences();
+            let expected = brute_force::man_optimal(&men_preferences, &women_preferences);
+
+            assert_eq!(
+                solver.solve(&men_preferences, &women_preferences),
+                expected,
+                "{:?}",
+                instance
+            );
+            assert_eq!(
+                solver.solve_with_preferences(
+                    men_preferences.keys().cloned(),
+                    &men_preferences,
+                    &women_preferences
+                ),
+                expected,
+                "{:?}",
+                instance
+            );
+        }
+    }
+}
+
+#[test]
+fn test_streamed_preferences_match_lists() {
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let streams =
+            Streams::new(|man: &u32| men_preferences.get(man).unwrap().clone().into_iter());
+
+        assert_eq!(
+            Solver::new().solve_with_preferences(
+                men_preferences.keys().cloned(),
+                &streams,
+                &women_preferences
+            ),
+            matchertools::gale_shapley(&men_preferences, &women_preferences),
+            "{:?}",
+            instance
+        );
+    }
+}
+
+#[test]
+fn test_master_list_preferences_match_lists() {
+    for seed in 0..40 {
+        let instance = generator::master_list(8, 8, 0.1 * (seed % 5) as f64, seed);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let master_list = MasterList::from_preferences(&women_preferences);
+        assert_eq!(master_list.to_preferences(), women_preferences);
+
+        assert_eq!(
+            Solver::new().solve_with_preferences(
+                men_preferences.keys().cloned(),
+                &men_preferences,
+                &master_list
+            ),
+            matchertools::gale_shapley(&men_preferences, &women_preferences),
+            "{:?}",
+            instance
+        );
+    }
+}
+
+#[test]
+fn test_reused_solver_matches_gale_shapley() {
+    let mut solver = Solver::new().capacity(6, 6);
+    for instance in instances() {
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+
+        assert_eq!(
+            solver.solve_reusing(&men_preferences, &women_preferences),
+            matchertools::gale_shapley(&men_preferences, &women_preferences),
+            "{:?}",
+            instance
+        );
+    }
+}
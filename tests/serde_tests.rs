@@ -0,0 +1,57 @@
+#![cfg(feature = "serde")]
+
+//! Round trips through JSON, and the documented schema of `Instance` and `Matching`
+
+use matchertools::{generator, Instance, Matching};
+
+#[test]
+fn test_instance_round_trip() {
+    for seed in 0..10 {
+        let instance = generator::incomplete(6, 5, 0.6, seed);
+
+        let json = serde_json::to_string(&instance).unwrap();
+        let read: Instance<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, instance);
+    }
+}
+
+#[test]
+fn test_matching_round_trip() {
+    for seed in 0..10 {
+        let instance = generator::uniform(6, 6, seed);
+        let men_preferences = instance.men_preferences();
+        let women_preferences = instance.women_preferences();
+        let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+        let matching = Matching::from_engagements(&engaged_man_woman);
+
+        let json = serde_json::to_string(&matching).unwrap();
+        let read: Matching<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, matching);
+        assert_eq!(read.engagements(), engaged_man_woman);
+    }
+}
+
+#[test]
+fn test_documented_schema() {
+    let instance: Instance<String> = serde_json::from_str(
+        r#"{
+            "men": { "julius": ["cleopatra", "boudica"], "antony": ["cleopatra"] },
+            "women": { "cleopatra": ["antony", "julius"], "boudica": ["julius"] }
+        }"#,
+    )
+    .unwrap();
+    let men_preferences = instance.men_preferences();
+    let women_preferences = instance.women_preferences();
+    let engaged_man_woman = matchertools::gale_shapley(&men_preferences, &women_preferences);
+
+    let matching = Matching::from_engagements(&engaged_man_woman);
+    assert_eq!(
+        serde_json::to_value(&matching).unwrap(),
+        serde_json::json!({ "julius": "boudica", "antony": "cleopatra" })
+    );
+
+    let unknown_field =
+        serde_json::from_str::<Instance<String>>(r#"{ "men": {}, "women": {}, "capacities": {} }"#);
+    assert!(unknown_field.is_err());
+    assert!(serde_json::from_str::<Instance<String>>(r#"{ "men": {} }"#).is_err());
+}